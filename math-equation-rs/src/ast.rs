@@ -1,30 +1,256 @@
 use std::fmt;
 use std::fmt::Debug;
 
+use crate::bigint::BigInt;
+
+// math_equations := math_equation ~ ((";" | newline) ~ math_equation)*
 // math_equation := expression ("=" ~ expression)?
-// expression := unary_expression ~ (binary_op ~ unary_expression)*
+// aligned_block := aligned_row ~ ("\\" ~ aligned_row)* ~ "\\"?
+// aligned_row := expression? ~ ("&"? ~ "=" ~ expression | "&" ~ relation_op ~ expression)*  (at most one "&", e.g. `a &= b`, `&= c` or `&<= d`)
+// relation_op := a binary_op other than "+", "-", "−", "*", "/", "^", "&&" and "||"
+// expression := unary_expression ~ (binary_op? ~ unary_expression)*  (a missing binary_op is an implicit "*")
 // unary_expression := primary_expression | prefix_expression | postfix_expression
-// primary_expression :=  number | identifier | array | function_call | "(" ~ expression ~ ")"
-// array := "[" ~ expression ~ ("," ~ expression)* ~ "]"
+// primary_expression := atom ~ ("_" ~ atom ~ ("^" ~ superscript)? | "^" ~ superscript ~ "_" ~ atom)?
+// superscript := atom ~ ("^" ~ unary_expression)*  (right associative, `x_i^2^3` is `x_i^(2^3)`)
+// atom := number | identifier | text | array | matrix | cases | big_operator | decoration | function_call | "(" ~ expression ~ ")"
+// text := "\"" ~ ([^"\\] | "\\" ~ ["\\])* ~ "\""
+// array := "[" ~ expression ~ ("," ~ expression)* ~ "]"  (an array of arrays is a matrix)
+// matrix := "mat(" ~ row ~ (";" ~ row)* ~ ")"
+// row := expression ~ ("," ~ expression)*
+// cases := "cases(" ~ case ~ ("," ~ case)* ~ ")"
+// case := expression ~ (text ~ expression?)?  (e.g. `-x "if" x < 0` or `0 "otherwise"`)
+// big_operator := ("sum" | "prod" | "integral" | "lim" | "∑" | "∏" | "∫") ~ ("_" ~ limit)? ~ ("^" ~ atom)? ~ term
+// limit := "(" ~ (identifier ~ "=")? ~ expression ~ ")" | atom  (e.g. `_(i=0)`, `_(x -> 0)` or `_a`)
+// term := unary_expression ~ (binary_op? ~ unary_expression)*  (only "*", "/", "^" and implicit "*")
+// decoration := ("hat" | "bar" | "tilde" | "vec" | "dot" | "ddot" | "overline" | "underbrace") ~ "(" ~ expression ~ ")"
+//               (with any other number of arguments, e.g. `vec(a, b)`, it is a function_call)
 // function_call := identifier ~ ("(" ~ (expression ~ ("," ~ c_expression)*)? ~ ")")+
-// prefix_expression := unary_op ~ primary_expression
-// postfix_expression := primary_expression ~ unary_op
+// prefix_expression := unary_op ~ unary_expression ~ ("^" ~ unary_expression)*  (`-a^2` is `-(a^2)`)
+// postfix_expression := primary_expression ~ postfix_op
 // binary_op := "+" | "-" | "*" | "/" | "^" | "==" | "!=" | "&lt;" | "&gt;" | "&lt;=" | "&gt;=" | "&amp;&amp;" | "||"
-// unary_op := "-" | "!" | "++" | "--"
-// identifier := [a-zA-Z_][a-zA-Z0-9_]*
+//            | "->" | "=>" | "<=>" | "~" | "~=" | ":=" | "<<" | ">>" | "in" | "subset"
+//            | "×" | "·" | "⋅" | "÷" | "−" | "≠" | "≤" | "≥" | "∧" | "∨"
+//            | "→" | "⇒" | "⇔" | "∼" | "≅" | "≔" | "≪" | "≫" | "∈" | "⊂"
+// unary_op := "-" | "−" | "!" | "¬" | "++" | "--"
+// postfix_op := "!" | "++" | "--"
+// number := "0x" ~ [0-9a-fA-F]+ | [0-9]+ ~ ("." ~ [0-9]+)? ~ ([eE] ~ [+-]? ~ [0-9]+)?
+// identifier := alphabetic ~ alphanumeric*  (Unicode letters and digits)
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct MathEquation {
     pub expressions: Vec<Node<Expression>>,
 }
 
+// Lines of a derivation such as `a &= b \ &= c`, set one below the other
+// and aligned at their `&`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AlignedBlock {
+    pub rows: Vec<Node<AlignedRow>>,
+}
+
+// An `=` chain whose first expression may be left out, as in `&= c`, to
+// continue the line above. The relation after the `&` may also be another
+// one, as in `&<= d`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AlignedRow {
+    pub lhs: Option<Node<Expression>>,
+    pub rhs: Vec<Node<Expression>>, // Each written after an `=`, or after `relation`
+    pub align: Option<usize>,       // The `&` stands before the relation of `rhs[align]`
+    pub relation: Option<Node<BinaryOperator>>, // Written after the `&` instead of `=`
+}
+
 #[derive(Debug, PartialEq, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum Expression {
     UnaryExpression(Node<UnaryExpression>),
     BinaryExpression(Node<BinaryExpression>),
     PrimaryExpression(Node<PrimaryExpression>),
 }
 
+// Constructors for building expressions outside the parser. Every node of the
+// built expression gets the same span, normally that of the source expression
+// it was derived from.
+impl Expression {
+    pub fn primary(span: Span, primary: PrimaryExpression) -> Node<Expression> {
+        Node::new(
+            span,
+            Expression::PrimaryExpression(Node::new(span, primary)),
+        )
+    }
+
+    pub fn number(span: Span, value: f64) -> Node<Expression> {
+        Expression::primary(
+            span,
+            PrimaryExpression::Number(Node::new(span, Number::from_f64(value))),
+        )
+    }
+
+    pub fn identifier(span: Span, name: &str) -> Node<Expression> {
+        Expression::primary(
+            span,
+            PrimaryExpression::Identifier(Node::new(span, name.to_string())),
+        )
+    }
+
+    pub fn grouped(span: Span, expr: Node<Expression>) -> Node<Expression> {
+        Expression::primary(span, PrimaryExpression::GroupedExpression(Box::new(expr)))
+    }
+
+    pub fn call(span: Span, name: &str, arguments: Vec<Node<Expression>>) -> Node<Expression> {
+        Expression::primary(
+            span,
+            PrimaryExpression::FunctionCall(Node::new(
+                span,
+                FunctionCall {
+                    name: Node::new(span, name.to_string()),
+                    arguments,
+                },
+            )),
+        )
+    }
+
+    pub fn unary(span: Span, op: UnaryOperator, expr: Node<Expression>) -> Node<Expression> {
+        Node::new(
+            span,
+            Expression::UnaryExpression(Node::new(
+                span,
+                UnaryExpression {
+                    op: Some(Node::new(span, op)),
+                    expr: Box::new(expr),
+                    is_prefix: true,
+                },
+            )),
+        )
+    }
+
+    // Operands are wrapped in parentheses where the operator precedence would
+    // otherwise read the expression differently.
+    pub fn binary(
+        span: Span,
+        lhs: Node<Expression>,
+        op: BinaryOperator,
+        rhs: Node<Expression>,
+    ) -> Node<Expression> {
+        let lhs = parenthesize(lhs, &op, false);
+        let rhs = parenthesize(rhs, &op, true);
+        Node::new(
+            span,
+            Expression::BinaryExpression(Node::new(
+                span,
+                BinaryExpression {
+                    lhs: Box::new(lhs),
+                    op: Node::new(span, op),
+                    rhs: Box::new(rhs),
+                    implicit: false,
+                },
+            )),
+        )
+    }
+
+    // A product written by juxtaposition, as in `2x`. Where the operands could
+    // not be read back that way, as in `x 2` or `a -b`, the `*` is written out.
+    pub fn juxtaposed(
+        span: Span,
+        lhs: Node<Expression>,
+        rhs: Node<Expression>,
+    ) -> Node<Expression> {
+        let mut product = Expression::binary(span, lhs, BinaryOperator::Mul, rhs);
+        if let Expression::BinaryExpression(binary) = &mut product.node {
+            binary.node.implicit =
+                !binary.node.lhs.ends_with_postfix() && starts_with_juxtaposable(&binary.node.rhs);
+        }
+        product
+    }
+}
+
+// Whether the expression starts with something the parser multiplies by
+// juxtaposition: a letter or an opening bracket, but not a digit or a sign.
+fn starts_with_juxtaposable(expression: &Node<Expression>) -> bool {
+    match &expression.node {
+        Expression::UnaryExpression(unary) => {
+            !unary.node.is_prefix && starts_with_juxtaposable(&unary.node.expr)
+        }
+        Expression::BinaryExpression(binary) => starts_with_juxtaposable(&binary.node.lhs),
+        Expression::PrimaryExpression(primary) => match &primary.node {
+            PrimaryExpression::Number(_) | PrimaryExpression::Error(_) => false,
+            PrimaryExpression::Attachment(attachment) => {
+                starts_with_juxtaposable(&attachment.node.base)
+            }
+            _ => true,
+        },
+    }
+}
+
+fn parenthesize(
+    operand: Node<Expression>,
+    parent: &BinaryOperator,
+    is_rhs: bool,
+) -> Node<Expression> {
+    let needs_parens = match &operand.node {
+        Expression::BinaryExpression(binary) => {
+            let child = binary.node.op.node.precedence();
+            let left_associative = parent.associativity() == Associativity::Left;
+            let parent = parent.precedence();
+            child < parent || (child == parent && is_rhs == left_associative)
+        }
+        // `-x^2` would read as the negation of a power.
+        Expression::UnaryExpression(_) => *parent == BinaryOperator::Pow && !is_rhs,
+        // The body of a big operator would take in a product it is the left
+        // operand of.
+        Expression::PrimaryExpression(primary) => {
+            matches!(primary.node, PrimaryExpression::BigOperator(_))
+                && !is_rhs
+                && parent.precedence() >= BinaryOperator::Mul.precedence()
+        }
+    };
+    if needs_parens {
+        Expression::grouped(operand.span, operand)
+    } else {
+        operand
+    }
+}
+
+impl Node<Expression> {
+    // The expression with any enclosing parentheses removed.
+    pub fn ungrouped(&self) -> &Node<Expression> {
+        if let Expression::PrimaryExpression(primary) = &self.node {
+            if let PrimaryExpression::GroupedExpression(inner) = &primary.node {
+                return inner.ungrouped();
+            }
+        }
+        self
+    }
+
+    // Whether the expression ends in a postfix operator, as `a--` does, after
+    // which nothing can be juxtaposed.
+    pub fn ends_with_postfix(&self) -> bool {
+        match &self.node {
+            Expression::UnaryExpression(unary) => {
+                !unary.node.is_prefix || unary.node.expr.ends_with_postfix()
+            }
+            Expression::BinaryExpression(binary) => binary.node.rhs.ends_with_postfix(),
+            Expression::PrimaryExpression(_) => false,
+        }
+    }
+
+    // The value of a numeric literal, possibly negated or parenthesized.
+    pub fn as_number(&self) -> Option<f64> {
+        match &self.ungrouped().node {
+            Expression::PrimaryExpression(primary) => match &primary.node {
+                PrimaryExpression::Number(number) => Some(number.node.to_f64()),
+                _ => None,
+            },
+            Expression::UnaryExpression(unary) => match unary.node.op.as_ref().map(|op| &op.node) {
+                Some(UnaryOperator::Neg) if unary.node.is_prefix => {
+                    unary.node.expr.as_number().map(|value| -value)
+                }
+                _ => None,
+            },
+            Expression::BinaryExpression(_) => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct UnaryExpression {
     pub op: Option<Node<UnaryOperator>>, // Option to handle primary expressions without an operator
@@ -37,15 +263,87 @@ pub struct BinaryExpression {
     pub lhs: Box<Node<Expression>>,
     pub op: Node<BinaryOperator>,
     pub rhs: Box<Node<Expression>>,
+    pub implicit: bool, // Written by juxtaposition, as in `2x`; always a `Mul`
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum PrimaryExpression {
-    Number(Node<i64>),
+    Number(Node<Number>),
     Identifier(Node<String>),
     Array(Node<Array>),
+    Matrix(Node<Matrix>),
+    Cases(Node<Cases>),
+    BigOperator(Node<BigOperator>),
+    Decoration(Node<Decoration>),
     FunctionCall(Node<FunctionCall>),
     GroupedExpression(Box<Node<Expression>>),
+    Attachment(Node<Attachment>),
+    Text(Node<String>),  // Quoted prose, with escapes already resolved
+    Error(Node<String>), // Placeholder for source text that failed to parse
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Number {
+    pub lexeme: String, // The literal exactly as written in the source
+    pub radix: Radix,
+    pub integer: String, // Digits before the decimal point, without a radix prefix
+    pub fraction: Option<String>, // Digits after the decimal point
+    pub exponent: Option<String>, // Signed decimal exponent, e.g. "-9" for 1e-9
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Radix {
+    Decimal,     // 42, 3.14, 6.02e23
+    Hexadecimal, // 0xFF
+}
+
+impl Number {
+    // Literal for a non-negative value, written in the shortest form that
+    // round-trips; negative values are expressed with a unary minus instead.
+    pub fn from_f64(value: f64) -> Number {
+        let lexeme = format!("{}", value);
+        let (integer, fraction) = match lexeme.split_once('.') {
+            Some((integer, fraction)) => (integer.to_string(), Some(fraction.to_string())),
+            None => (lexeme.clone(), None),
+        };
+        Number {
+            lexeme,
+            radix: Radix::Decimal,
+            integer,
+            fraction,
+            exponent: None,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self.radix {
+            Radix::Decimal => self.lexeme.parse().unwrap_or(f64::NAN),
+            Radix::Hexadecimal => self.integer.chars().fold(0.0, |acc, c| {
+                acc * 16.0 + c.to_digit(16).map_or(f64::NAN, f64::from)
+            }),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.fraction.is_none() && self.exponent.is_none()
+    }
+
+    // Exact value of an integer literal, however many digits it has.
+    pub fn to_bigint(&self) -> Option<BigInt> {
+        if !self.is_integer() {
+            return None;
+        }
+        match self.radix {
+            Radix::Decimal => BigInt::from_str_radix(&self.integer, 10),
+            Radix::Hexadecimal => BigInt::from_str_radix(&self.integer, 16),
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.lexeme)
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -53,6 +351,107 @@ pub struct Array {
     pub elements: Vec<Node<Expression>>,
 }
 
+// A base with a subscript, a superscript or both, as in `x_i^2`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Attachment {
+    pub base: Box<Node<Expression>>,
+    pub bottom: Option<Box<Node<Expression>>>,
+    pub top: Option<Box<Node<Expression>>>,
+}
+
+impl Attachment {
+    // Name of an indexed variable such as `x_1` or `x_i`, which evaluation
+    // treats as a single variable of that name. The superscript is not part
+    // of the name; it raises the variable to a power.
+    pub fn variable_name(&self) -> Option<String> {
+        self.indexed_variable_name(|_| None)
+    }
+
+    // Like `variable_name`, but a subscript naming an index with an integer
+    // value is replaced by that value, so inside `sum_(i=1)^3` the name of
+    // `x_i` is `x_1`, `x_2` or `x_3`.
+    pub fn indexed_variable_name(&self, index: impl Fn(&str) -> Option<f64>) -> Option<String> {
+        let Expression::PrimaryExpression(base) = &self.base.node else {
+            return None;
+        };
+        let PrimaryExpression::Identifier(base) = &base.node else {
+            return None;
+        };
+        let Expression::PrimaryExpression(bottom) = &self.bottom.as_ref()?.ungrouped().node else {
+            return None;
+        };
+        match &bottom.node {
+            PrimaryExpression::Identifier(name) => match index(&name.node) {
+                Some(value) if value.fract() == 0.0 => Some(format!("{}_{}", base.node, value)),
+                _ => Some(format!("{}_{}", base.node, name.node)),
+            },
+            PrimaryExpression::Number(index) => Some(format!("{}_{}", base.node, index.node)),
+            _ => None,
+        }
+    }
+}
+
+// Written as `mat(a, b; c, d)` or `[[a, b], [c, d]]`. The parser ensures that
+// all rows have the same length.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Matrix {
+    pub rows: Vec<Node<Array>>,
+}
+
+// A piecewise definition. Its value is that of the first case whose condition
+// holds; a case without a condition, such as `0 "otherwise"`, always does.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cases {
+    pub cases: Vec<Node<Case>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Case {
+    pub value: Node<Expression>,
+    pub text: Option<Node<String>>, // The words before the condition, e.g. "if"
+    pub condition: Option<Node<Expression>>,
+}
+
+// A sum, product, integral or limit, as in `sum_(i=0)^n i^2`. The body
+// extends over the following product, so `sum_i a_i b_i + c` adds `c` to the
+// sum.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BigOperator {
+    pub op: Node<BigOperatorKind>,
+    pub index: Option<Node<String>>, // The `i` of `sum_(i=0)`, bound within the body
+    pub lower: Option<Box<Node<Expression>>>,
+    pub upper: Option<Box<Node<Expression>>>,
+    pub body: Box<Node<Expression>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum BigOperatorKind {
+    Sum,      // sum ∑
+    Prod,     // prod ∏
+    Integral, // integral ∫
+    Lim,      // lim
+}
+
+// An accent or a line or brace drawn over or under an expression, as in
+// `vec(v)` or `overline(a + b)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Decoration {
+    pub kind: Node<DecorationKind>,
+    pub base: Box<Node<Expression>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum DecorationKind {
+    Hat,        // hat(x)
+    Bar,        // bar(x)
+    Tilde,      // tilde(x)
+    Vec,        // vec(v), an arrow
+    Dot,        // dot(x), the time derivative
+    Ddot,       // ddot(x), the second time derivative
+    Overline,   // overline(a + b)
+    Underbrace, // underbrace(a + b)
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunctionCall {
     pub name: Node<String>,
@@ -69,19 +468,68 @@ pub enum UnaryOperator {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum BinaryOperator {
-    Add, // +
-    Sub, // -
-    Mul, // *
-    Div, // /
-    Pow, // ^
-    Eq,  // ==
-    Ne,  // !=
-    Lt,  // <
-    Gt,  // >
-    Le,  // <=
-    Ge,  // >=
-    And, // &&
-    Or,  // ||
+    Add,         // +
+    Sub,         // -
+    Mul,         // *
+    Div,         // /
+    Pow,         // ^
+    Eq,          // ==
+    Ne,          // !=
+    Lt,          // <
+    Gt,          // >
+    Le,          // <=
+    Ge,          // >=
+    And,         // &&
+    Or,          // ||
+    Arrow,       // ->
+    Implies,     // =>
+    Iff,         // <=>
+    Sim,         // ~
+    Cong,        // ~=
+    Define,      // :=
+    MuchLess,    // <<
+    MuchGreater, // >>
+    In,          // in
+    Subset,      // subset
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Associativity {
+    Left,  // a - b - c = (a - b) - c
+    Right, // a ^ b ^ c = a ^ (b ^ c)
+}
+
+// The default precedence and associativity, which the parser's
+// `OperatorTable` can override. Operators with a higher precedence bind
+// tighter.
+impl BinaryOperator {
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperator::Define => 1,                          // :=
+            BinaryOperator::Iff => 2,                             // <=>
+            BinaryOperator::Implies | BinaryOperator::Arrow => 3, // =>, ->
+            BinaryOperator::Or => 4,                              // ||
+            BinaryOperator::And => 5,                             // &&
+            BinaryOperator::Eq | BinaryOperator::Ne => 6,         // ==, !=
+            BinaryOperator::Lt | BinaryOperator::Gt | BinaryOperator::Le | BinaryOperator::Ge => 7, // <, >, <=, >=
+            BinaryOperator::MuchLess | BinaryOperator::MuchGreater => 7, // <<, >>
+            BinaryOperator::Sim | BinaryOperator::Cong => 7,             // ~, ~=
+            BinaryOperator::In | BinaryOperator::Subset => 7,            // in, subset
+            BinaryOperator::Add | BinaryOperator::Sub => 8,              // +, -
+            BinaryOperator::Mul | BinaryOperator::Div => 9,              // *, /
+            BinaryOperator::Pow => 10,                                   // ^
+        }
+    }
+
+    pub fn associativity(&self) -> Associativity {
+        match self {
+            // a => b => c reads as a => (b => c), and likewise for the others.
+            BinaryOperator::Pow | BinaryOperator::Implies | BinaryOperator::Arrow => {
+                Associativity::Right
+            }
+            _ => Associativity::Left,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -131,6 +579,7 @@ pub struct Span {
 }
 
 impl Span {
+    #[allow(clippy::self_named_constructors)]
     pub fn span(start: usize, end: usize) -> Span {
         Span { start, end }
     }
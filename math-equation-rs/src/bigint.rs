@@ -0,0 +1,343 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+// Arbitrary-precision signed integer used to evaluate integer literals that do
+// not fit in a machine word. The magnitude is stored as little-endian base 2^32
+// limbs with no trailing zero limbs, so zero is an empty vector.
+
+// Upper bound on the size of a power, about 315,000 decimal digits.
+pub const MAX_POW_BITS: u64 = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> BigInt {
+        BigInt {
+            negative: false,
+            magnitude: Vec::new(),
+        }
+    }
+
+    pub fn from_str_radix(digits: &str, radix: u32) -> Option<BigInt> {
+        if digits.is_empty() {
+            return None;
+        }
+        let mut magnitude = Vec::new();
+        for c in digits.chars() {
+            let digit = c.to_digit(radix)?;
+            mul_small(&mut magnitude, radix);
+            add_small(&mut magnitude, digit);
+        }
+        Some(BigInt::from_parts(false, magnitude))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn to_u32(&self) -> Option<u32> {
+        match self.magnitude.as_slice() {
+            _ if self.negative => None,
+            [] => Some(0),
+            [limb] => Some(*limb),
+            _ => None,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let value = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0.0, |acc, &limb| acc * 4294967296.0 + f64::from(limb));
+        if self.negative {
+            -value
+        } else {
+            value
+        }
+    }
+
+    // Number of bits in the magnitude.
+    pub fn bits(&self) -> u64 {
+        match self.magnitude.last() {
+            Some(last) => self.magnitude.len() as u64 * 32 - u64::from(last.leading_zeros()),
+            None => 0,
+        }
+    }
+
+    // Like `pow`, but returns `None` instead of computing a result of more
+    // than `MAX_POW_BITS` bits.
+    pub fn checked_pow(&self, exponent: u32) -> Option<BigInt> {
+        if self.bits() > 1 && self.bits() * u64::from(exponent) > MAX_POW_BITS {
+            return None;
+        }
+        Some(self.pow(exponent))
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> BigInt {
+        let mut base = self.clone();
+        let mut result = BigInt::from(1);
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            base = &base * &base;
+            exponent >>= 1;
+        }
+        result
+    }
+
+    // Truncating division, like the integer division of the primitive types.
+    // Returns `None` when dividing by zero.
+    pub fn div_rem(&self, divisor: &BigInt) -> Option<(BigInt, BigInt)> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let mut quotient = vec![0; self.magnitude.len()];
+        let mut remainder = Vec::new();
+        for i in (0..self.magnitude.len() * 32).rev() {
+            shl1(&mut remainder);
+            if (self.magnitude[i / 32] >> (i % 32)) & 1 == 1 {
+                add_small(&mut remainder, 1);
+            }
+            if cmp_magnitude(&remainder, &divisor.magnitude) != Ordering::Less {
+                sub_magnitude(&mut remainder, &divisor.magnitude);
+                quotient[i / 32] |= 1 << (i % 32);
+            }
+        }
+        Some((
+            BigInt::from_parts(self.negative != divisor.negative, quotient),
+            BigInt::from_parts(self.negative, remainder),
+        ))
+    }
+
+    fn from_parts(negative: bool, mut magnitude: Vec<u32>) -> BigInt {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        BigInt {
+            negative: negative && !magnitude.is_empty(),
+            magnitude,
+        }
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> BigInt {
+        let abs = value.unsigned_abs();
+        BigInt::from_parts(value < 0, vec![abs as u32, (abs >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.magnitude)
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            let mut magnitude = self.magnitude.clone();
+            add_magnitude(&mut magnitude, &other.magnitude);
+            return BigInt::from_parts(self.negative, magnitude);
+        }
+        // Opposite signs: subtract the smaller magnitude from the larger one and
+        // keep the sign of the larger.
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => {
+                let mut magnitude = other.magnitude.clone();
+                sub_magnitude(&mut magnitude, &self.magnitude);
+                BigInt::from_parts(other.negative, magnitude)
+            }
+            _ => {
+                let mut magnitude = self.magnitude.clone();
+                sub_magnitude(&mut magnitude, &other.magnitude);
+                BigInt::from_parts(self.negative, magnitude)
+            }
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other.clone()
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut magnitude = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, &a) in self.magnitude.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.magnitude.iter().enumerate() {
+                let t = u64::from(a) * u64::from(b) + u64::from(magnitude[i + j]) + carry;
+                magnitude[i + j] = t as u32;
+                carry = t >> 32;
+            }
+            magnitude[i + other.magnitude.len()] = carry as u32;
+        }
+        BigInt::from_parts(self.negative != other.negative, magnitude)
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // Peel off base 10^9 chunks, least significant first.
+        let mut chunks = Vec::new();
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            chunks.push(div_small(&mut magnitude, 1_000_000_000));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        if let Some(first) = chunks.next() {
+            write!(f, "{}", first)?;
+        }
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &mut Vec<u32>, b: &[u32]) {
+    if a.len() < b.len() {
+        a.resize(b.len(), 0);
+    }
+    let mut carry = 0u64;
+    for (i, limb) in a.iter_mut().enumerate() {
+        let t = u64::from(*limb) + u64::from(b.get(i).copied().unwrap_or(0)) + carry;
+        *limb = t as u32;
+        carry = t >> 32;
+    }
+    if carry > 0 {
+        a.push(carry as u32);
+    }
+}
+
+// Requires `a >= b`.
+fn sub_magnitude(a: &mut Vec<u32>, b: &[u32]) {
+    let mut borrow = 0i64;
+    for (i, limb) in a.iter_mut().enumerate() {
+        let mut t = i64::from(*limb) - i64::from(b.get(i).copied().unwrap_or(0)) - borrow;
+        borrow = 0;
+        if t < 0 {
+            t += 1 << 32;
+            borrow = 1;
+        }
+        *limb = t as u32;
+    }
+    while a.last() == Some(&0) {
+        a.pop();
+    }
+}
+
+fn add_small(a: &mut Vec<u32>, b: u32) {
+    add_magnitude(a, &[b]);
+    while a.last() == Some(&0) {
+        a.pop();
+    }
+}
+
+fn mul_small(a: &mut Vec<u32>, b: u32) {
+    let mut carry = 0u64;
+    for limb in a.iter_mut() {
+        let t = u64::from(*limb) * u64::from(b) + carry;
+        *limb = t as u32;
+        carry = t >> 32;
+    }
+    if carry > 0 {
+        a.push(carry as u32);
+    }
+}
+
+fn div_small(a: &mut Vec<u32>, b: u32) -> u32 {
+    let mut remainder = 0u64;
+    for limb in a.iter_mut().rev() {
+        let t = (remainder << 32) | u64::from(*limb);
+        *limb = (t / u64::from(b)) as u32;
+        remainder = t % u64::from(b);
+    }
+    while a.last() == Some(&0) {
+        a.pop();
+    }
+    remainder as u32
+}
+
+fn shl1(a: &mut Vec<u32>) {
+    let mut carry = 0;
+    for limb in a.iter_mut() {
+        let next = *limb >> 31;
+        *limb = (*limb << 1) | carry;
+        carry = next;
+    }
+    if carry > 0 {
+        a.push(carry);
+    }
+}
+
+#[test]
+fn test_bigint_arithmetic() {
+    let mersenne = &BigInt::from(2).pow(127) - &BigInt::from(1);
+    assert_eq!(
+        mersenne.to_string(),
+        "170141183460469231731687303715884105727"
+    );
+    let parsed = BigInt::from_str_radix("170141183460469231731687303715884105727", 10).unwrap();
+    assert_eq!(parsed, mersenne);
+    let (quotient, remainder) = parsed.div_rem(&BigInt::from(-1_000_000_007)).unwrap();
+    assert_eq!(
+        &(&quotient * &BigInt::from(-1_000_000_007)) + &remainder,
+        parsed
+    );
+    assert!(quotient.is_negative());
+    assert_eq!(BigInt::from_str_radix("ff", 16), Some(BigInt::from(255)));
+    assert_eq!((mersenne.bits(), BigInt::zero().bits()), (127, 0));
+    assert_eq!(BigInt::from(2).checked_pow(1 << 20), None);
+}
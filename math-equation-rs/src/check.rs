@@ -0,0 +1,333 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ast::{Expression, MathEquation, Node, PrimaryExpression, Span};
+use crate::eval::{eval, EvalError, Value};
+
+// Largest number of values the index of a sum with literal bounds is stepped
+// through to find the subscripted variables it uses.
+const MAX_INDEX_VALUES: f64 = 1000.0;
+
+// Checks that every `=` link of a math equation holds numerically. Variables
+// bound in the environment keep their value; any other variable is sampled
+// uniformly from the domain, so identities such as `(a + b)^2 = a^2 + 2*a*b + b^2`
+// can be verified without picking values by hand.
+
+pub struct CheckOptions {
+    pub tolerance: f64, // Relative tolerance, absolute for values smaller than 1
+    pub samples: usize, // Number of random assignments tried for unbound variables
+    pub domain: (f64, f64),
+    pub seed: u64,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        CheckOptions {
+            tolerance: 1e-9,
+            samples: 32,
+            domain: (-10.0, 10.0),
+            seed: 0x5eed,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LinkCheck {
+    pub lhs: Span,
+    pub rhs: Span,
+    pub result: LinkResult,
+}
+
+#[derive(Debug)]
+pub enum LinkResult {
+    Holds,
+    Fails {
+        lhs: Value,
+        rhs: Value,
+        bindings: HashMap<String, f64>,
+    },
+    // Neither side was defined at any of the sampled points.
+    Undetermined,
+    Error(EvalError),
+}
+
+impl LinkCheck {
+    pub fn holds(&self) -> bool {
+        matches!(self.result, LinkResult::Holds)
+    }
+
+    // The span to point at when reporting this link: the side that disagrees
+    // with the one before it, or the subexpression that failed to evaluate.
+    pub fn failing_span(&self) -> Option<Span> {
+        match &self.result {
+            LinkResult::Holds | LinkResult::Undetermined => None,
+            LinkResult::Fails { .. } => Some(self.rhs),
+            LinkResult::Error(err) => Some(err.span),
+        }
+    }
+}
+
+pub fn check_equation(
+    math_equation: &Node<MathEquation>,
+    env: &HashMap<String, f64>,
+    options: &CheckOptions,
+) -> Vec<LinkCheck> {
+    let sides = &math_equation.node.expressions;
+    let mut free = BTreeSet::new();
+    for side in sides {
+        free_variables(side, &HashMap::new(), &mut free);
+    }
+    free.retain(|name| !env.contains_key(name) && name != "pi" && name != "e");
+
+    let mut results: Vec<Option<LinkResult>> = sides.iter().skip(1).map(|_| None).collect();
+    let mut defined = vec![false; results.len()];
+    let samples = if free.is_empty() { 1 } else { options.samples };
+    let mut rng = SplitMix64(options.seed);
+    let mut bindings = env.clone();
+    for _ in 0..samples {
+        for name in &free {
+            let (low, high) = options.domain;
+            bindings.insert(name.clone(), low + (high - low) * rng.next_f64());
+        }
+        let values: Vec<_> = sides.iter().map(|side| eval(side, &bindings)).collect();
+        for (i, result) in results.iter_mut().enumerate() {
+            if result.is_some() {
+                continue;
+            }
+            match (&values[i], &values[i + 1]) {
+                (Err(err), _) | (_, Err(err)) => {
+                    *result = Some(LinkResult::Error(err.clone()));
+                }
+                (Ok(lhs), Ok(rhs)) => match agree(*lhs, *rhs, options.tolerance) {
+                    Some(true) => defined[i] = true,
+                    Some(false) => {
+                        *result = Some(LinkResult::Fails {
+                            lhs: *lhs,
+                            rhs: *rhs,
+                            bindings: free
+                                .iter()
+                                .map(|name| (name.clone(), bindings[name]))
+                                .collect(),
+                        });
+                    }
+                    None => {}
+                },
+            }
+        }
+    }
+
+    results
+        .into_iter()
+        .enumerate()
+        .map(|(i, result)| LinkCheck {
+            lhs: sides[i].span,
+            rhs: sides[i + 1].span,
+            result: result.unwrap_or(if defined[i] {
+                LinkResult::Holds
+            } else {
+                LinkResult::Undetermined
+            }),
+        })
+        .collect()
+}
+
+// `None` when either side is undefined (NaN or infinite) at this point, in which
+// case the sample says nothing about the link.
+fn agree(lhs: Value, rhs: Value, tolerance: f64) -> Option<bool> {
+    match (lhs, rhs) {
+        (Value::Number(lhs), Value::Number(rhs)) => {
+            if !lhs.is_finite() || !rhs.is_finite() {
+                return None;
+            }
+            Some((lhs - rhs).abs() <= tolerance * lhs.abs().max(rhs.abs()).max(1.0))
+        }
+        (Value::Bool(lhs), Value::Bool(rhs)) => Some(lhs == rhs),
+        _ => Some(false),
+    }
+}
+
+// `indices` holds the values of the sum indices in scope, which are
+// substituted into subscripts: `x_i` with `i = 2` is the variable `x_2`.
+fn free_variables(
+    expression: &Node<Expression>,
+    indices: &HashMap<String, f64>,
+    free: &mut BTreeSet<String>,
+) {
+    match &expression.node {
+        Expression::UnaryExpression(unary) => free_variables(&unary.node.expr, indices, free),
+        Expression::BinaryExpression(binary) => {
+            free_variables(&binary.node.lhs, indices, free);
+            free_variables(&binary.node.rhs, indices, free);
+        }
+        Expression::PrimaryExpression(primary) => match &primary.node {
+            PrimaryExpression::Number(_)
+            | PrimaryExpression::Text(_)
+            | PrimaryExpression::Error(_) => {}
+            PrimaryExpression::Identifier(identifier) => {
+                if !indices.contains_key(&identifier.node) {
+                    free.insert(identifier.node.clone());
+                }
+            }
+            PrimaryExpression::Array(array) => {
+                for element in &array.node.elements {
+                    free_variables(element, indices, free);
+                }
+            }
+            PrimaryExpression::Matrix(matrix) => {
+                for row in &matrix.node.rows {
+                    for element in &row.node.elements {
+                        free_variables(element, indices, free);
+                    }
+                }
+            }
+            // The index of a sum is bound within its body. With literal bounds
+            // every value it takes is substituted, so that `sum_(i=1)^3 x_i`
+            // uses `x_1`, `x_2` and `x_3`, unless there are too many values.
+            PrimaryExpression::BigOperator(big_operator) => {
+                let node = &big_operator.node;
+                let bounds = node
+                    .lower
+                    .as_ref()
+                    .and_then(|lower| lower.as_number())
+                    .zip(node.upper.as_ref().and_then(|upper| upper.as_number()))
+                    .filter(|(lower, upper)| upper - lower < MAX_INDEX_VALUES);
+                match (&node.index, bounds) {
+                    (Some(index), Some((lower, upper))) => {
+                        let mut indices = indices.clone();
+                        let mut value = lower.ceil();
+                        while value <= upper {
+                            indices.insert(index.node.clone(), value);
+                            free_variables(&node.body, &indices, free);
+                            value += 1.0;
+                        }
+                    }
+                    _ => {
+                        let mut body = BTreeSet::new();
+                        free_variables(&node.body, indices, &mut body);
+                        if let Some(index) = &node.index {
+                            body.remove(&index.node);
+                        }
+                        free.extend(body);
+                    }
+                }
+                for bound in [&big_operator.node.lower, &big_operator.node.upper]
+                    .into_iter()
+                    .flatten()
+                {
+                    free_variables(bound, indices, free);
+                }
+            }
+            PrimaryExpression::Cases(cases) => {
+                for case in &cases.node.cases {
+                    free_variables(&case.node.value, indices, free);
+                    if let Some(condition) = &case.node.condition {
+                        free_variables(condition, indices, free);
+                    }
+                }
+            }
+            PrimaryExpression::FunctionCall(function_call) => {
+                for argument in &function_call.node.arguments {
+                    free_variables(argument, indices, free);
+                }
+            }
+            PrimaryExpression::GroupedExpression(expr) => free_variables(expr, indices, free),
+            PrimaryExpression::Decoration(decoration) => {
+                free_variables(&decoration.node.base, indices, free)
+            }
+            PrimaryExpression::Attachment(attachment) => {
+                match attachment
+                    .node
+                    .indexed_variable_name(|name| indices.get(name).copied())
+                {
+                    Some(name) => {
+                        free.insert(name);
+                    }
+                    None => {
+                        free_variables(&attachment.node.base, indices, free);
+                        if let Some(bottom) = &attachment.node.bottom {
+                            free_variables(bottom, indices, free);
+                        }
+                    }
+                }
+                if let Some(top) = &attachment.node.top {
+                    free_variables(top, indices, free);
+                }
+            }
+        },
+    }
+}
+
+// Small deterministic generator so checks are reproducible between CI runs.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[test]
+fn test_check_equation_with_bindings() {
+    let parser = crate::parser::Parser::new("a^2 + b^2 = c^2");
+    let math_equation = parser.math_equation().unwrap();
+    let env = HashMap::from([
+        ("a".to_string(), 3.0),
+        ("b".to_string(), 4.0),
+        ("c".to_string(), 5.0),
+    ]);
+    let checks = check_equation(&math_equation, &env, &CheckOptions::default());
+    assert!(checks.iter().all(LinkCheck::holds));
+
+    let env = HashMap::from([
+        ("a".to_string(), 3.0),
+        ("b".to_string(), 4.0),
+        ("c".to_string(), 6.0),
+    ]);
+    let checks = check_equation(&math_equation, &env, &CheckOptions::default());
+    let failing = checks[0].failing_span().unwrap();
+    assert_eq!((failing.start, failing.end), (12, 15));
+
+    // A subscript that is not the index of a sum names its own variable.
+    let parser = crate::parser::Parser::new("a_n = 2 n");
+    let math_equation = parser.math_equation().unwrap();
+    let env = HashMap::from([("n".to_string(), 3.0), ("a_n".to_string(), 6.0)]);
+    let checks = check_equation(&math_equation, &env, &CheckOptions::default());
+    assert!(checks.iter().all(LinkCheck::holds));
+}
+
+#[test]
+fn test_check_equation_by_sampling() {
+    let parser = crate::parser::Parser::new("(x + 1)^2 = x^2 + 2*x + 1 = x^2 + x + 1");
+    let math_equation = parser.math_equation().unwrap();
+    let checks = check_equation(&math_equation, &HashMap::new(), &CheckOptions::default());
+    assert!(checks[0].holds());
+    assert!(matches!(checks[1].result, LinkResult::Fails { .. }));
+}
+
+#[test]
+fn test_check_sum_over_many_values() {
+    let parser = crate::parser::Parser::new("sum_(i=1)^(1e8) x_i = 0");
+    let math_equation = parser.math_equation().unwrap();
+    let mut free = BTreeSet::new();
+    free_variables(
+        &math_equation.node.expressions[0],
+        &HashMap::new(),
+        &mut free,
+    );
+    assert_eq!(free, BTreeSet::from(["x_i".to_string()]));
+}
+
+#[test]
+fn test_check_sum_over_subscripts() {
+    let parser = crate::parser::Parser::new("sum_(i=1)^3 i x_i = x_1 + 2 x_2 + 3 x_3");
+    let math_equation = parser.math_equation().unwrap();
+    let checks = check_equation(&math_equation, &HashMap::new(), &CheckOptions::default());
+    assert!(checks[0].holds());
+}
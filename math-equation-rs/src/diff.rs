@@ -0,0 +1,488 @@
+use crate::ast::{
+    Attachment, BigOperator, BigOperatorKind, BinaryOperator, Case, Cases, DecorationKind,
+    Expression, FunctionCall, Node, PrimaryExpression, Span, UnaryOperator,
+};
+
+// Symbolic differentiation. The derivative is built from nodes carrying the
+// span of the subexpression they were derived from, and trivial terms such as
+// `0 * u` or `u^1` are folded while building so the result stays readable.
+
+#[derive(Debug)]
+pub struct DiffError {
+    pub span: Span,
+    pub message: String,
+}
+
+pub fn diff(expression: &Node<Expression>, var: &str) -> Result<Node<Expression>, DiffError> {
+    let span = expression.span;
+    match &expression.node {
+        Expression::UnaryExpression(unary) => {
+            let d = diff(&unary.node.expr, var)?;
+            match unary.node.op.as_ref().map(|op| &op.node) {
+                None | Some(UnaryOperator::Inc) | Some(UnaryOperator::Dec) => Ok(d),
+                Some(UnaryOperator::Neg) => Ok(neg(span, d)),
+                Some(UnaryOperator::Not) => Err(diff_err(
+                    span,
+                    "Cannot differentiate a logical expression".to_string(),
+                )),
+            }
+        }
+        Expression::BinaryExpression(binary) => {
+            let u = &binary.node.lhs;
+            let v = &binary.node.rhs;
+            match binary.node.op.node {
+                BinaryOperator::Add => Ok(add(span, diff(u, var)?, diff(v, var)?)),
+                BinaryOperator::Sub => Ok(sub(span, diff(u, var)?, diff(v, var)?)),
+                // (u v)' = u' v + u v'
+                BinaryOperator::Mul => Ok(add(
+                    span,
+                    mul(span, diff(u, var)?, strip(v)),
+                    mul(span, strip(u), diff(v, var)?),
+                )),
+                // (u / v)' = (u' v - u v') / v^2
+                BinaryOperator::Div => Ok(div(
+                    span,
+                    sub(
+                        span,
+                        mul(span, diff(u, var)?, strip(v)),
+                        mul(span, strip(u), diff(v, var)?),
+                    ),
+                    pow(span, strip(v), Expression::number(span, 2.0)),
+                )),
+                BinaryOperator::Pow => diff_pow(span, u, v, var),
+                _ => Err(diff_err(
+                    binary.node.op.span,
+                    format!("Cannot differentiate {:?}", binary.node.op.node),
+                )),
+            }
+        }
+        Expression::PrimaryExpression(primary) => match &primary.node {
+            PrimaryExpression::Number(_) => Ok(Expression::number(span, 0.0)),
+            PrimaryExpression::Identifier(identifier) => Ok(Expression::number(
+                span,
+                if identifier.node == var { 1.0 } else { 0.0 },
+            )),
+            PrimaryExpression::GroupedExpression(expr) => diff(expr, var),
+            PrimaryExpression::FunctionCall(function_call) => {
+                diff_function_call(span, &function_call.node, var)
+            }
+            PrimaryExpression::Attachment(attachment) => {
+                diff_attachment(span, &attachment.node, var)
+            }
+            PrimaryExpression::BigOperator(big_operator) => {
+                diff_big_operator(span, &big_operator.node, var)
+            }
+            PrimaryExpression::Decoration(decoration) => match decoration.node.kind.node {
+                DecorationKind::Underbrace => diff(&decoration.node.base, var),
+                _ if !depends_on(&decoration.node.base, var) => Ok(Expression::number(span, 0.0)),
+                _ => Err(diff_err(
+                    span,
+                    "Cannot differentiate a decorated expression".to_string(),
+                )),
+            },
+            // Each piece is differentiated on its own; the derivative at the
+            // boundaries between pieces is not checked.
+            PrimaryExpression::Cases(cases) => {
+                let mut derived = Vec::new();
+                for case in &cases.node.cases {
+                    derived.push(Node::new(
+                        case.span,
+                        Case {
+                            value: diff(&case.node.value, var)?,
+                            text: case.node.text.clone(),
+                            condition: case.node.condition.clone(),
+                        },
+                    ));
+                }
+                Ok(Node::new(
+                    span,
+                    Expression::PrimaryExpression(Node::new(
+                        span,
+                        PrimaryExpression::Cases(Node::new(cases.span, Cases { cases: derived })),
+                    )),
+                ))
+            }
+            PrimaryExpression::Array(_) => {
+                Err(diff_err(span, "Cannot differentiate an array".to_string()))
+            }
+            PrimaryExpression::Matrix(_) => {
+                Err(diff_err(span, "Cannot differentiate a matrix".to_string()))
+            }
+            PrimaryExpression::Text(_) => {
+                Err(diff_err(span, "Cannot differentiate text".to_string()))
+            }
+            PrimaryExpression::Error(_) => Err(diff_err(
+                span,
+                "Cannot differentiate an expression with syntax errors".to_string(),
+            )),
+        },
+    }
+}
+
+// A sum is differentiated term by term, as long as its bounds don't depend on
+// the variable. Other big operators only have a derivative where they are
+// constant.
+fn diff_big_operator(
+    span: Span,
+    big_operator: &BigOperator,
+    var: &str,
+) -> Result<Node<Expression>, DiffError> {
+    let bounds_depend = [&big_operator.lower, &big_operator.upper]
+        .into_iter()
+        .flatten()
+        .any(|bound| depends_on(bound, var));
+    let binds_var = big_operator
+        .index
+        .as_ref()
+        .is_some_and(|index| index.node == var);
+    if !bounds_depend && (binds_var || !depends_on(&big_operator.body, var)) {
+        return Ok(Expression::number(span, 0.0));
+    }
+    if bounds_depend || big_operator.op.node != BigOperatorKind::Sum {
+        return Err(diff_err(
+            big_operator.op.span,
+            format!("Cannot differentiate {:?}", big_operator.op.node),
+        ));
+    }
+    // The body only extends over a product, so a derived sum of terms has to
+    // be parenthesized.
+    let body = diff(&big_operator.body, var)?;
+    let body = match &body.node {
+        Expression::BinaryExpression(binary)
+            if binary.node.op.node.precedence() < BinaryOperator::Mul.precedence() =>
+        {
+            Expression::grouped(span, body)
+        }
+        _ => body,
+    };
+    Ok(Expression::primary(
+        span,
+        PrimaryExpression::BigOperator(Node::new(
+            span,
+            BigOperator {
+                body: Box::new(body),
+                ..big_operator.clone()
+            },
+        )),
+    ))
+}
+
+fn diff_pow(
+    span: Span,
+    u: &Node<Expression>,
+    v: &Node<Expression>,
+    var: &str,
+) -> Result<Node<Expression>, DiffError> {
+    if !depends_on(v, var) {
+        // (u^n)' = n u^(n - 1) u'
+        let exponent = match v.as_number() {
+            Some(n) => number(v.span, n - 1.0),
+            None => sub(v.span, strip(v), Expression::number(v.span, 1.0)),
+        };
+        return Ok(mul(
+            span,
+            mul(span, strip(v), pow(span, strip(u), exponent)),
+            diff(u, var)?,
+        ));
+    }
+    if !depends_on(u, var) {
+        // (a^v)' = a^v ln(a) v'
+        return Ok(mul(
+            span,
+            mul(
+                span,
+                pow(span, strip(u), strip(v)),
+                Expression::call(u.span, "ln", vec![strip(u)]),
+            ),
+            diff(v, var)?,
+        ));
+    }
+    // (u^v)' = u^v (v' ln(u) + v u' / u)
+    Ok(mul(
+        span,
+        pow(span, strip(u), strip(v)),
+        add(
+            span,
+            mul(
+                span,
+                diff(v, var)?,
+                Expression::call(u.span, "ln", vec![strip(u)]),
+            ),
+            div(span, mul(span, strip(v), diff(u, var)?), strip(u)),
+        ),
+    ))
+}
+
+// An indexed variable such as `x_i` is independent of `x`; a superscript is
+// differentiated as a power.
+fn diff_attachment(
+    span: Span,
+    attachment: &Attachment,
+    var: &str,
+) -> Result<Node<Expression>, DiffError> {
+    if let Some(top) = &attachment.top {
+        let base = Expression::primary(
+            span,
+            PrimaryExpression::Attachment(Node::new(
+                span,
+                Attachment {
+                    top: None,
+                    ..attachment.clone()
+                },
+            )),
+        );
+        return diff_pow(span, &base, top, var);
+    }
+    match attachment.variable_name() {
+        Some(name) => Ok(Expression::number(
+            span,
+            if name == var { 1.0 } else { 0.0 },
+        )),
+        None if ![&attachment.base]
+            .into_iter()
+            .chain(&attachment.bottom)
+            .any(|child| depends_on(child, var)) =>
+        {
+            Ok(Expression::number(span, 0.0))
+        }
+        None => Err(diff_err(
+            span,
+            "Cannot differentiate a subscripted expression".to_string(),
+        )),
+    }
+}
+
+fn diff_function_call(
+    span: Span,
+    function_call: &FunctionCall,
+    var: &str,
+) -> Result<Node<Expression>, DiffError> {
+    let name = function_call.name.node.as_str();
+    let [u] = function_call.arguments.as_slice() else {
+        return Err(diff_err(
+            span,
+            format!(
+                "Cannot differentiate {} with {} arguments",
+                name,
+                function_call.arguments.len()
+            ),
+        ));
+    };
+    let call = |name: &str| Expression::call(span, name, vec![strip(u)]);
+    // Derivative of the outer function, evaluated at u.
+    let outer = match name {
+        "sin" => call("cos"),
+        "cos" => neg(span, call("sin")),
+        "exp" => call("exp"),
+        "ln" => div(span, Expression::number(span, 1.0), strip(u)),
+        "sqrt" => div(
+            span,
+            Expression::number(span, 1.0),
+            mul(span, Expression::number(span, 2.0), call("sqrt")),
+        ),
+        _ => {
+            return Err(diff_err(
+                function_call.name.span,
+                format!("Cannot differentiate unknown function '{}'", name),
+            ))
+        }
+    };
+    Ok(mul(span, outer, diff(u, var)?))
+}
+
+fn depends_on(expression: &Node<Expression>, var: &str) -> bool {
+    match &expression.node {
+        Expression::UnaryExpression(unary) => depends_on(&unary.node.expr, var),
+        Expression::BinaryExpression(binary) => {
+            depends_on(&binary.node.lhs, var) || depends_on(&binary.node.rhs, var)
+        }
+        Expression::PrimaryExpression(primary) => match &primary.node {
+            PrimaryExpression::Number(_)
+            | PrimaryExpression::Text(_)
+            | PrimaryExpression::Error(_) => false,
+            PrimaryExpression::Identifier(identifier) => identifier.node == var,
+            PrimaryExpression::GroupedExpression(expr) => depends_on(expr, var),
+            PrimaryExpression::FunctionCall(function_call) => function_call
+                .node
+                .arguments
+                .iter()
+                .any(|argument| depends_on(argument, var)),
+            PrimaryExpression::Array(array) => array
+                .node
+                .elements
+                .iter()
+                .any(|element| depends_on(element, var)),
+            PrimaryExpression::Matrix(matrix) => matrix
+                .node
+                .rows
+                .iter()
+                .flat_map(|row| &row.node.elements)
+                .any(|element| depends_on(element, var)),
+            PrimaryExpression::BigOperator(big_operator) => {
+                let binds_var = big_operator
+                    .node
+                    .index
+                    .as_ref()
+                    .is_some_and(|index| index.node == var);
+                [&big_operator.node.lower, &big_operator.node.upper]
+                    .into_iter()
+                    .flatten()
+                    .any(|bound| depends_on(bound, var))
+                    || (!binds_var && depends_on(&big_operator.node.body, var))
+            }
+            PrimaryExpression::Decoration(decoration) => depends_on(&decoration.node.base, var),
+            PrimaryExpression::Cases(cases) => cases.node.cases.iter().any(|case| {
+                depends_on(&case.node.value, var)
+                    || case
+                        .node
+                        .condition
+                        .as_ref()
+                        .is_some_and(|condition| depends_on(condition, var))
+            }),
+            PrimaryExpression::Attachment(attachment) => {
+                let indexed = match attachment.node.variable_name() {
+                    Some(name) => name == var,
+                    None => [&attachment.node.base]
+                        .into_iter()
+                        .chain(&attachment.node.bottom)
+                        .any(|child| depends_on(child, var)),
+                };
+                indexed
+                    || attachment
+                        .node
+                        .top
+                        .as_ref()
+                        .is_some_and(|top| depends_on(top, var))
+            }
+        },
+    }
+}
+
+// Source parentheses are dropped when an operand is reused;
+// `Expression::binary` adds them back wherever the new parent requires them.
+fn strip(expression: &Node<Expression>) -> Node<Expression> {
+    expression.ungrouped().clone()
+}
+
+fn add(span: Span, u: Node<Expression>, v: Node<Expression>) -> Node<Expression> {
+    match (u.as_number(), v.as_number()) {
+        (Some(a), Some(b)) => number(span, a + b),
+        (Some(0.0), _) => v,
+        (_, Some(0.0)) => u,
+        _ => Expression::binary(span, u, BinaryOperator::Add, v),
+    }
+}
+
+fn sub(span: Span, u: Node<Expression>, v: Node<Expression>) -> Node<Expression> {
+    match (u.as_number(), v.as_number()) {
+        (Some(a), Some(b)) => number(span, a - b),
+        (Some(0.0), _) => neg(span, v),
+        (_, Some(0.0)) => u,
+        _ => Expression::binary(span, u, BinaryOperator::Sub, v),
+    }
+}
+
+fn mul(span: Span, u: Node<Expression>, v: Node<Expression>) -> Node<Expression> {
+    match (u.as_number(), v.as_number()) {
+        (Some(a), Some(b)) => number(span, a * b),
+        (Some(0.0), _) | (_, Some(0.0)) => Expression::number(span, 0.0),
+        (Some(1.0), _) => v,
+        (_, Some(1.0)) => u,
+        // Keep numeric coefficients in front.
+        (None, Some(_)) => Expression::binary(span, v, BinaryOperator::Mul, u),
+        _ => Expression::binary(span, u, BinaryOperator::Mul, v),
+    }
+}
+
+fn div(span: Span, u: Node<Expression>, v: Node<Expression>) -> Node<Expression> {
+    match (u.as_number(), v.as_number()) {
+        (Some(0.0), _) => Expression::number(span, 0.0),
+        (_, Some(1.0)) => u,
+        _ => Expression::binary(span, u, BinaryOperator::Div, v),
+    }
+}
+
+fn pow(span: Span, u: Node<Expression>, v: Node<Expression>) -> Node<Expression> {
+    match v.as_number() {
+        Some(0.0) => Expression::number(span, 1.0),
+        Some(1.0) => u,
+        _ => Expression::binary(span, u, BinaryOperator::Pow, v),
+    }
+}
+
+fn neg(span: Span, u: Node<Expression>) -> Node<Expression> {
+    if let Some(a) = u.as_number() {
+        return number(span, -a);
+    }
+    if let Expression::UnaryExpression(unary) = &u.node {
+        if let Some(UnaryOperator::Neg) = unary.node.op.as_ref().map(|op| &op.node) {
+            return strip(&unary.node.expr);
+        }
+    }
+    Expression::unary(span, UnaryOperator::Neg, u)
+}
+
+fn number(span: Span, value: f64) -> Node<Expression> {
+    if value == 0.0 {
+        // Avoid writing out a negative zero.
+        Expression::number(span, 0.0)
+    } else if value < 0.0 {
+        Expression::unary(span, UnaryOperator::Neg, Expression::number(span, -value))
+    } else {
+        Expression::number(span, value)
+    }
+}
+
+fn diff_err(span: Span, message: String) -> DiffError {
+    DiffError { span, message }
+}
+
+#[test]
+fn test_diff_polynomial() {
+    let parser = crate::parser::Parser::new("x^3 + 3*x - y");
+    let expression = parser.expression().unwrap();
+    let derivative = diff(&expression, "x").unwrap();
+    assert_eq!(
+        crate::typst::expression_to_typst(&derivative),
+        "3 dot x^(2) + 3"
+    );
+}
+
+#[test]
+fn test_diff_matches_finite_difference() {
+    use std::collections::HashMap;
+
+    let parser = crate::parser::Parser::new("sin(x) * x^2 / sqrt(x + 1) - exp(2 * x) + ln(x) ^ x");
+    let expression = parser.expression().unwrap();
+    let derivative = diff(&expression, "x").unwrap();
+    for x in [1.5, 2.0, 2.5] {
+        let at = |x: f64| HashMap::from([("x".to_string(), x)]);
+        let h = 1e-6;
+        let numeric = (crate::eval::eval_number(&expression, &at(x + h)).unwrap()
+            - crate::eval::eval_number(&expression, &at(x - h)).unwrap())
+            / (2.0 * h);
+        let symbolic = crate::eval::eval_number(&derivative, &at(x)).unwrap();
+        assert!(
+            (numeric - symbolic).abs() < 1e-4,
+            "{} != {}",
+            numeric,
+            symbolic
+        );
+    }
+}
+
+#[test]
+fn test_diff_negative_and_fractional_powers() {
+    let cases = [("x^(-1)", "-1 dot x^(-2)"), ("x^0.5", "0.5 dot x^(-0.5)")];
+    for (input, expected) in cases {
+        let parser = crate::parser::Parser::new(input);
+        let expression = parser.expression().unwrap();
+        let derivative = diff(&expression, "x").unwrap();
+        assert_eq!(
+            crate::typst::expression_to_typst(&derivative),
+            expected,
+            "{}",
+            input
+        );
+    }
+}
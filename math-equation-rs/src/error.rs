@@ -0,0 +1,154 @@
+use std::fmt;
+
+use crate::ast::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Literal(String), // A fixed piece of syntax such as ")" or ","
+    Number,
+    Identifier,
+    BinaryOperator,
+    UnaryOperator,
+    EndOfInput,
+    Row(usize),       // A matrix row with this many entries
+    Arguments(usize), // A call with this many arguments
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::Literal(literal) => write!(f, "'{}'", literal),
+            TokenKind::Number => write!(f, "number"),
+            TokenKind::Identifier => write!(f, "identifier"),
+            TokenKind::BinaryOperator => write!(f, "binary operator"),
+            TokenKind::UnaryOperator => write!(f, "unary operator"),
+            TokenKind::EndOfInput => write!(f, "end of input"),
+            TokenKind::Row(columns) => write!(f, "row of {}", entries(*columns)),
+            TokenKind::Arguments(1) => write!(f, "1 argument"),
+            TokenKind::Arguments(count) => write!(f, "{} arguments", count),
+        }
+    }
+}
+
+pub(crate) fn entries(count: usize) -> String {
+    match count {
+        1 => "1 entry".to_string(),
+        _ => format!("{} entries", count),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParserError {
+    pub span: Span,
+    pub expected: Vec<TokenKind>,
+    pub found: Option<String>, // None at the end of the input
+    pub hint: Option<String>,
+}
+
+impl ParserError {
+    pub fn with_hint(mut self, hint: String) -> ParserError {
+        self.hint = Some(hint);
+        self
+    }
+
+    pub fn message(&self) -> String {
+        let found = match &self.found {
+            Some(found) => format!("{:?}", found),
+            None => TokenKind::EndOfInput.to_string(),
+        };
+        match self.expected.as_slice() {
+            [] => format!("unexpected {}", found),
+            [expected] => format!("expected {} but found {}", expected, found),
+            [init @ .., last] => format!(
+                "expected {} or {} but found {}",
+                init.iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                last,
+                found
+            ),
+        }
+    }
+
+    // Renders the error together with the offending source line, with the span
+    // underlined:
+    //
+    //   error: expected ')' but found ']'
+    //    --> 1:7
+    //     |
+    //   1 | (a + b]
+    //     |       ^
+    pub fn render(&self, source: &str) -> String {
+        let (line, column) = line_col(source, self.span.start);
+        let line_start = source[..self.span.start.min(source.len())]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        let line_end = source[line_start..]
+            .find('\n')
+            .map_or(source.len(), |i| line_start + i);
+        let text = source[line_start..line_end].trim_end_matches('\r');
+        let underline_end = self.span.end.clamp(self.span.start, line_end);
+        let width = source[self.span.start.min(line_end)..underline_end]
+            .chars()
+            .count()
+            .max(1);
+        let gutter = " ".repeat(line.to_string().len());
+
+        let mut out = format!("error: {}\n", self.message());
+        out.push_str(&format!("{} --> {}:{}\n", gutter, line, column));
+        out.push_str(&format!("{} |\n", gutter));
+        out.push_str(&format!("{} | {}\n", line, text));
+        out.push_str(&format!(
+            "{} | {}{}\n",
+            gutter,
+            " ".repeat(column - 1),
+            "^".repeat(width)
+        ));
+        if let Some(hint) = &self.hint {
+            out.push_str(&format!("{} = hint: {}\n", gutter, hint));
+        }
+        out
+    }
+}
+
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())?;
+        if let Some(hint) = &self.hint {
+            write!(f, " ({})", hint)?;
+        }
+        Ok(())
+    }
+}
+
+// One-based line and column (in characters) of a byte offset.
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+#[test]
+fn test_render() {
+    let source = "x = 1\ny = (a + b]";
+    let error = ParserError {
+        span: Span::span(16, 17),
+        expected: vec![
+            TokenKind::BinaryOperator,
+            TokenKind::Literal(")".to_string()),
+        ],
+        found: Some("]".to_string()),
+        hint: Some("the '(' at 2:5 is not closed".to_string()),
+    };
+    assert_eq!(
+        error.render(source),
+        "error: expected binary operator or ')' but found \"]\"\n  \
+         --> 2:11\n  \
+         |\n\
+         2 | y = (a + b]\n  \
+         |           ^\n  \
+         = hint: the '(' at 2:5 is not closed\n"
+    );
+}
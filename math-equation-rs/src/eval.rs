@@ -0,0 +1,701 @@
+use std::collections::HashMap;
+
+use crate::ast::{
+    BigOperator, BigOperatorKind, BinaryExpression, BinaryOperator, DecorationKind, Expression,
+    FunctionCall, Node, PrimaryExpression, Span, UnaryExpression, UnaryOperator,
+};
+use crate::bigint::BigInt;
+
+#[derive(Debug, Clone)]
+pub struct EvalError {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Value {
+    Number(f64),
+    Bool(bool),
+}
+
+fn eval_err(span: Span, message: String) -> EvalError {
+    EvalError { span, message }
+}
+
+// Largest number of terms a sum or product is evaluated with.
+const MAX_TERMS: i64 = 1_000_000;
+
+// The variables an expression is evaluated with. The indices of the enclosing
+// sums and products are kept apart from `env`, since only they are substituted
+// into subscripts: within `sum_i`, `x_i` stands for `x_1`, `x_2` and so on,
+// while `a_n` stays the variable `a_n` whatever `n` is bound to.
+struct Scope<'a> {
+    env: &'a HashMap<String, f64>,
+    indices: HashMap<String, f64>,
+}
+
+// Evaluates an expression with floating point arithmetic. Comparisons and the
+// logical operators produce booleans; identifiers are looked up in `env`, and
+// `pi` and `e` fall back to their usual constants when not bound there.
+pub fn eval(expression: &Node<Expression>, env: &HashMap<String, f64>) -> Result<Value, EvalError> {
+    evaluate(expression, &Scope::new(env))
+}
+
+pub fn eval_number(
+    expression: &Node<Expression>,
+    env: &HashMap<String, f64>,
+) -> Result<f64, EvalError> {
+    evaluate_number(expression, &Scope::new(env))
+}
+
+pub fn eval_bool(
+    expression: &Node<Expression>,
+    env: &HashMap<String, f64>,
+) -> Result<bool, EvalError> {
+    evaluate_bool(expression, &Scope::new(env))
+}
+
+impl<'a> Scope<'a> {
+    fn new(env: &'a HashMap<String, f64>) -> Scope<'a> {
+        Scope {
+            env,
+            indices: HashMap::new(),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<f64> {
+        self.indices
+            .get(name)
+            .or_else(|| self.env.get(name))
+            .copied()
+    }
+}
+
+fn evaluate(expression: &Node<Expression>, scope: &Scope) -> Result<Value, EvalError> {
+    match &expression.node {
+        Expression::UnaryExpression(unary) => eval_unary(&unary.node, scope),
+        Expression::BinaryExpression(binary) => eval_binary(&binary.node, scope),
+        Expression::PrimaryExpression(primary) => match &primary.node {
+            PrimaryExpression::Number(number) => Ok(Value::Number(number.node.to_f64())),
+            PrimaryExpression::Identifier(identifier) => {
+                lookup(identifier.span, &identifier.node, scope).map(Value::Number)
+            }
+            PrimaryExpression::GroupedExpression(expr) => evaluate(expr, scope),
+            PrimaryExpression::Attachment(attachment) => {
+                let base =
+                    match attachment
+                        .node
+                        .indexed_variable_name(|name| scope.indices.get(name).copied())
+                    {
+                        Some(name) => lookup(attachment.node.base.span, &name, scope)?,
+                        None if attachment.node.bottom.is_none() => {
+                            evaluate_number(&attachment.node.base, scope)?
+                        }
+                        None => return Err(eval_err(
+                            attachment.span,
+                            "Only identifiers with a name or number as subscript can be evaluated"
+                                .to_string(),
+                        )),
+                    };
+                match &attachment.node.top {
+                    Some(top) => Ok(Value::Number(base.powf(evaluate_number(top, scope)?))),
+                    None => Ok(Value::Number(base)),
+                }
+            }
+            PrimaryExpression::FunctionCall(function_call) => {
+                eval_function_call(function_call, scope)
+            }
+            PrimaryExpression::BigOperator(big_operator) => eval_big_operator(big_operator, scope),
+            // An underbrace only annotates; the other decorations change the
+            // meaning of what they decorate.
+            PrimaryExpression::Decoration(decoration) => match decoration.node.kind.node {
+                DecorationKind::Underbrace => evaluate(&decoration.node.base, scope),
+                _ => Err(eval_err(
+                    primary.span,
+                    "Decorated expressions cannot be evaluated".to_string(),
+                )),
+            },
+            PrimaryExpression::Cases(cases) => {
+                // The first case whose condition holds wins; a case without a
+                // condition always applies.
+                for case in &cases.node.cases {
+                    let applies = match &case.node.condition {
+                        Some(condition) => evaluate_bool(condition, scope)?,
+                        None => true,
+                    };
+                    if applies {
+                        return evaluate(&case.node.value, scope);
+                    }
+                }
+                Err(eval_err(cases.span, "No case applies".to_string()))
+            }
+            PrimaryExpression::Array(_) => Err(eval_err(
+                primary.span,
+                "Arrays cannot be evaluated to a scalar".to_string(),
+            )),
+            PrimaryExpression::Matrix(_) => Err(eval_err(
+                primary.span,
+                "Matrices cannot be evaluated to a scalar".to_string(),
+            )),
+            PrimaryExpression::Text(_) => Err(eval_err(
+                primary.span,
+                "Text cannot be evaluated".to_string(),
+            )),
+            PrimaryExpression::Error(_) => Err(eval_err(
+                primary.span,
+                "Cannot evaluate an expression with syntax errors".to_string(),
+            )),
+        },
+    }
+}
+
+fn lookup(span: Span, name: &str, scope: &Scope) -> Result<f64, EvalError> {
+    match (scope.get(name), name) {
+        (Some(value), _) => Ok(value),
+        (None, "pi") => Ok(std::f64::consts::PI),
+        (None, "e") => Ok(std::f64::consts::E),
+        (None, name) => Err(eval_err(span, format!("Unbound variable '{}'", name))),
+    }
+}
+
+fn evaluate_number(expression: &Node<Expression>, scope: &Scope) -> Result<f64, EvalError> {
+    match evaluate(expression, scope)? {
+        Value::Number(value) => Ok(value),
+        Value::Bool(_) => Err(eval_err(
+            expression.span,
+            "Expected a number but found a boolean".to_string(),
+        )),
+    }
+}
+
+fn evaluate_bool(expression: &Node<Expression>, scope: &Scope) -> Result<bool, EvalError> {
+    match evaluate(expression, scope)? {
+        Value::Bool(value) => Ok(value),
+        Value::Number(_) => Err(eval_err(
+            expression.span,
+            "Expected a boolean but found a number".to_string(),
+        )),
+    }
+}
+
+fn eval_unary(unary: &UnaryExpression, scope: &Scope) -> Result<Value, EvalError> {
+    match unary.op.as_ref().map(|op| &op.node) {
+        None => evaluate(&unary.expr, scope),
+        Some(UnaryOperator::Not) => Ok(Value::Bool(!evaluate_bool(&unary.expr, scope)?)),
+        Some(UnaryOperator::Neg) => Ok(Value::Number(-evaluate_number(&unary.expr, scope)?)),
+        Some(UnaryOperator::Inc) => Ok(Value::Number(evaluate_number(&unary.expr, scope)? + 1.0)),
+        Some(UnaryOperator::Dec) => Ok(Value::Number(evaluate_number(&unary.expr, scope)? - 1.0)),
+    }
+}
+
+fn eval_binary(binary: &BinaryExpression, scope: &Scope) -> Result<Value, EvalError> {
+    // The logical operators short-circuit, so the right-hand side may be
+    // ill-defined when the left-hand side already decides the result.
+    match binary.op.node {
+        BinaryOperator::And => {
+            return Ok(Value::Bool(
+                evaluate_bool(&binary.lhs, scope)? && evaluate_bool(&binary.rhs, scope)?,
+            ))
+        }
+        BinaryOperator::Or => {
+            return Ok(Value::Bool(
+                evaluate_bool(&binary.lhs, scope)? || evaluate_bool(&binary.rhs, scope)?,
+            ))
+        }
+        BinaryOperator::Implies => {
+            return Ok(Value::Bool(
+                !evaluate_bool(&binary.lhs, scope)? || evaluate_bool(&binary.rhs, scope)?,
+            ))
+        }
+        BinaryOperator::Iff => {
+            return Ok(Value::Bool(
+                evaluate_bool(&binary.lhs, scope)? == evaluate_bool(&binary.rhs, scope)?,
+            ))
+        }
+        BinaryOperator::Arrow
+        | BinaryOperator::Sim
+        | BinaryOperator::Cong
+        | BinaryOperator::Define
+        | BinaryOperator::MuchLess
+        | BinaryOperator::MuchGreater
+        | BinaryOperator::In
+        | BinaryOperator::Subset => {
+            return Err(eval_err(
+                binary.op.span,
+                format!("{:?} cannot be evaluated", binary.op.node),
+            ))
+        }
+        BinaryOperator::Eq | BinaryOperator::Ne => {
+            let equal = match (evaluate(&binary.lhs, scope)?, evaluate(&binary.rhs, scope)?) {
+                (Value::Number(lhs), Value::Number(rhs)) => lhs == rhs,
+                (Value::Bool(lhs), Value::Bool(rhs)) => lhs == rhs,
+                _ => {
+                    return Err(eval_err(
+                        binary.op.span,
+                        "Cannot compare a number with a boolean".to_string(),
+                    ))
+                }
+            };
+            return Ok(Value::Bool(equal == (binary.op.node == BinaryOperator::Eq)));
+        }
+        _ => {}
+    }
+    let lhs = evaluate_number(&binary.lhs, scope)?;
+    let rhs = evaluate_number(&binary.rhs, scope)?;
+    Ok(match binary.op.node {
+        BinaryOperator::Add => Value::Number(lhs + rhs),
+        BinaryOperator::Sub => Value::Number(lhs - rhs),
+        BinaryOperator::Mul => Value::Number(lhs * rhs),
+        BinaryOperator::Div => Value::Number(lhs / rhs),
+        BinaryOperator::Pow => Value::Number(lhs.powf(rhs)),
+        BinaryOperator::Lt => Value::Bool(lhs < rhs),
+        BinaryOperator::Gt => Value::Bool(lhs > rhs),
+        BinaryOperator::Le => Value::Bool(lhs <= rhs),
+        BinaryOperator::Ge => Value::Bool(lhs >= rhs),
+        _ => unreachable!("handled above"),
+    })
+}
+
+// Sums and products over an integer range, as in `sum_(i=1)^n i`, are
+// evaluated term by term with the index bound in the body, up to `MAX_TERMS`
+// terms.
+fn eval_big_operator(big_operator: &Node<BigOperator>, scope: &Scope) -> Result<Value, EvalError> {
+    let node = &big_operator.node;
+    let (mut acc, combine): (f64, fn(f64, f64) -> f64) = match node.op.node {
+        BigOperatorKind::Sum => (0.0, |acc, term| acc + term),
+        BigOperatorKind::Prod => (1.0, |acc, term| acc * term),
+        _ => {
+            return Err(eval_err(
+                node.op.span,
+                format!("{:?} cannot be evaluated", node.op.node),
+            ))
+        }
+    };
+    let (Some(index), Some(lower), Some(upper)) = (&node.index, &node.lower, &node.upper) else {
+        return Err(eval_err(
+            big_operator.span,
+            "Only sums and products with an index and both bounds can be evaluated".to_string(),
+        ));
+    };
+    let lower = integer_bound(lower, scope)?;
+    let upper = integer_bound(upper, scope)?;
+    if upper.saturating_sub(lower) >= MAX_TERMS {
+        return Err(eval_err(
+            big_operator.span,
+            format!(
+                "{:?} from {} to {} has more than {} terms",
+                node.op.node, lower, upper, MAX_TERMS
+            ),
+        ));
+    }
+    let mut scope = Scope {
+        env: scope.env,
+        indices: scope.indices.clone(),
+    };
+    for i in lower..=upper {
+        scope.indices.insert(index.node.clone(), i as f64);
+        acc = combine(acc, evaluate_number(&node.body, &scope)?);
+    }
+    Ok(Value::Number(acc))
+}
+
+fn integer_bound(bound: &Node<Expression>, scope: &Scope) -> Result<i64, EvalError> {
+    let value = evaluate_number(bound, scope)?;
+    if value.fract() != 0.0 {
+        return Err(eval_err(
+            bound.span,
+            format!("Expected an integer bound but found {}", value),
+        ));
+    }
+    Ok(value as i64)
+}
+
+fn eval_function_call(
+    function_call: &Node<FunctionCall>,
+    scope: &Scope,
+) -> Result<Value, EvalError> {
+    let name = function_call.node.name.node.as_str();
+    let arguments = function_call
+        .node
+        .arguments
+        .iter()
+        .map(|argument| evaluate_number(argument, scope))
+        .collect::<Result<Vec<_>, _>>()?;
+    let unary = |f: fn(f64) -> f64| match arguments.as_slice() {
+        [x] => Ok(Value::Number(f(*x))),
+        _ => Err(eval_err(
+            function_call.span,
+            format!("{} expects 1 argument but got {}", name, arguments.len()),
+        )),
+    };
+    let binary = |f: fn(f64, f64) -> Result<f64, String>| match arguments.as_slice() {
+        [x, y] => f(*x, *y)
+            .map(Value::Number)
+            .map_err(|message| eval_err(function_call.span, message)),
+        _ => Err(eval_err(
+            function_call.span,
+            format!("{} expects 2 arguments but got {}", name, arguments.len()),
+        )),
+    };
+    let variadic = |f: fn(f64, f64) -> f64| match arguments.split_first() {
+        Some((first, rest)) => Ok(Value::Number(rest.iter().copied().fold(*first, f))),
+        None => Err(eval_err(
+            function_call.span,
+            format!("{} expects at least 1 argument", name),
+        )),
+    };
+    match name {
+        "sin" => unary(f64::sin),
+        "cos" => unary(f64::cos),
+        "tan" => unary(f64::tan),
+        "exp" => unary(f64::exp),
+        "ln" => unary(f64::ln),
+        "sqrt" => unary(f64::sqrt),
+        "abs" | "norm" => unary(f64::abs),
+        "floor" => unary(f64::floor),
+        "ceil" => unary(f64::ceil),
+        "frac" => binary(|numerator, denominator| Ok(numerator / denominator)),
+        "root" => binary(root),
+        "binom" => binary(binomial),
+        "min" => variadic(f64::min),
+        "max" => variadic(f64::max),
+        _ => Err(eval_err(
+            function_call.node.name.span,
+            format!("Unknown function '{}'", name),
+        )),
+    }
+}
+
+// The real root, which for a negative radicand only exists if the index is an
+// odd integer, as in `root(3, -8) = -2`.
+fn root(index: f64, radicand: f64) -> Result<f64, String> {
+    if radicand >= 0.0 {
+        Ok(radicand.powf(index.recip()))
+    } else if index.fract() == 0.0 && index % 2.0 != 0.0 {
+        Ok(-(-radicand).powf(index.recip()))
+    } else {
+        Err(format!(
+            "root({}, {}) is not a real number",
+            index, radicand
+        ))
+    }
+}
+
+// The number of ways to choose k of n things. As C(n, k) = C(n, n - k), at
+// most n/2 factors are multiplied, and since each of them is at least 2 the
+// product overflows after about a thousand of them.
+fn binomial(n: f64, k: f64) -> Result<f64, String> {
+    if n.fract() != 0.0 || k.fract() != 0.0 || n < 0.0 {
+        return Err(format!(
+            "binom({}, {}) is only defined for integers with n >= 0",
+            n, k
+        ));
+    }
+    if k < 0.0 || k > n {
+        return Ok(0.0);
+    }
+    let k = k.min(n - k);
+    let mut acc = 1.0_f64;
+    let mut i = 0.0;
+    while i < k && acc.is_finite() {
+        acc = acc * (n - i) / (i + 1.0);
+        i += 1.0;
+    }
+    if !acc.is_finite() {
+        return Err(format!("binom({}, {}) is too large", n, k));
+    }
+    Ok(acc)
+}
+
+// Evaluates an expression exactly over arbitrary-precision integers. Only
+// integer literals and the arithmetic operators are supported, and a division
+// that leaves a remainder is reported as an error rather than truncated.
+pub fn eval_integer(
+    expression: &Node<Expression>,
+    env: &HashMap<String, BigInt>,
+) -> Result<BigInt, EvalError> {
+    match &expression.node {
+        Expression::UnaryExpression(unary) => eval_integer_unary(&unary.node, env),
+        Expression::BinaryExpression(binary) => {
+            eval_integer_binary(expression.span, &binary.node, env)
+        }
+        Expression::PrimaryExpression(primary) => match &primary.node {
+            PrimaryExpression::Number(number) => number.node.to_bigint().ok_or_else(|| {
+                eval_err(
+                    number.span,
+                    format!("{} is not an integer literal", number.node),
+                )
+            }),
+            PrimaryExpression::Identifier(identifier) => {
+                env.get(&identifier.node).cloned().ok_or_else(|| {
+                    eval_err(
+                        identifier.span,
+                        format!("Unbound variable '{}'", identifier.node),
+                    )
+                })
+            }
+            PrimaryExpression::GroupedExpression(expr) => eval_integer(expr, env),
+            PrimaryExpression::Attachment(attachment) => {
+                let Some(name) = attachment.node.variable_name() else {
+                    return Err(eval_err(
+                        attachment.span,
+                        "Expected an integer expression".to_string(),
+                    ));
+                };
+                let base = env.get(&name).cloned().ok_or_else(|| {
+                    eval_err(
+                        attachment.node.base.span,
+                        format!("Unbound variable '{}'", name),
+                    )
+                })?;
+                match &attachment.node.top {
+                    Some(top) => integer_pow(&base, &eval_integer(top, env)?, top.span),
+                    None => Ok(base),
+                }
+            }
+            PrimaryExpression::Array(_)
+            | PrimaryExpression::Matrix(_)
+            | PrimaryExpression::Cases(_)
+            | PrimaryExpression::BigOperator(_)
+            | PrimaryExpression::Decoration(_)
+            | PrimaryExpression::FunctionCall(_)
+            | PrimaryExpression::Text(_)
+            | PrimaryExpression::Error(_) => Err(eval_err(
+                primary.span,
+                "Expected an integer expression".to_string(),
+            )),
+        },
+    }
+}
+
+fn eval_integer_unary(
+    unary: &UnaryExpression,
+    env: &HashMap<String, BigInt>,
+) -> Result<BigInt, EvalError> {
+    let value = eval_integer(&unary.expr, env)?;
+    match unary.op.as_ref().map(|op| (&op.node, op.span)) {
+        None => Ok(value),
+        Some((UnaryOperator::Neg, _)) => Ok(-value),
+        Some((UnaryOperator::Inc, _)) => Ok(&value + &BigInt::from(1)),
+        Some((UnaryOperator::Dec, _)) => Ok(&value - &BigInt::from(1)),
+        Some((UnaryOperator::Not, span)) => Err(eval_err(
+            span,
+            "Logical not is not an integer operation".to_string(),
+        )),
+    }
+}
+
+fn eval_integer_binary(
+    span: Span,
+    binary: &BinaryExpression,
+    env: &HashMap<String, BigInt>,
+) -> Result<BigInt, EvalError> {
+    let lhs = eval_integer(&binary.lhs, env)?;
+    let rhs = eval_integer(&binary.rhs, env)?;
+    match binary.op.node {
+        BinaryOperator::Add => Ok(&lhs + &rhs),
+        BinaryOperator::Sub => Ok(&lhs - &rhs),
+        BinaryOperator::Mul => Ok(&lhs * &rhs),
+        BinaryOperator::Div => match lhs.div_rem(&rhs) {
+            None => Err(eval_err(span, "Division by zero".to_string())),
+            Some((quotient, remainder)) if remainder.is_zero() => Ok(quotient),
+            Some(_) => Err(eval_err(
+                span,
+                format!("{} is not divisible by {}", lhs, rhs),
+            )),
+        },
+        BinaryOperator::Pow => integer_pow(&lhs, &rhs, binary.rhs.span),
+        _ => Err(eval_err(
+            binary.op.span,
+            format!("{:?} is not an integer operation", binary.op.node),
+        )),
+    }
+}
+
+fn integer_pow(base: &BigInt, exponent: &BigInt, span: Span) -> Result<BigInt, EvalError> {
+    match exponent.to_u32() {
+        Some(exponent) => base.checked_pow(exponent).ok_or_else(|| {
+            eval_err(
+                span,
+                format!("{}^{} is too large to evaluate exactly", base, exponent),
+            )
+        }),
+        None => Err(eval_err(
+            span,
+            format!(
+                "Exponent {} must be a non-negative 32-bit integer",
+                exponent
+            ),
+        )),
+    }
+}
+
+#[test]
+fn test_eval() {
+    let parser = crate::parser::Parser::new("max(sqrt(x^2), 1) / 2 + -abs(y)");
+    let expression = parser.expression().unwrap();
+    let env = HashMap::from([("x".to_string(), -6.0), ("y".to_string(), -0.5)]);
+    assert_eq!(eval(&expression, &env).unwrap(), Value::Number(2.5));
+}
+
+#[test]
+fn test_eval_logic() {
+    let parser = crate::parser::Parser::new("x >= 0 && sin(x) != 1 || x == pi");
+    let expression = parser.expression().unwrap();
+    let env = HashMap::from([("x".to_string(), 0.0)]);
+    assert_eq!(eval(&expression, &env).unwrap(), Value::Bool(true));
+    let env = HashMap::new();
+    let err = eval(&expression, &env).unwrap_err();
+    assert_eq!(err.message, "Unbound variable 'x'");
+}
+
+#[test]
+fn test_eval_integer() {
+    let parser = crate::parser::Parser::new("2^127 - 1 = 170141183460469231731687303715884105727");
+    let math_equation = parser.math_equation().unwrap();
+    let env = HashMap::new();
+    let lhs = eval_integer(&math_equation.node.expressions[0], &env).unwrap();
+    let rhs = eval_integer(&math_equation.node.expressions[1], &env).unwrap();
+    assert_eq!(lhs, rhs);
+    assert_eq!(lhs.to_string(), "170141183460469231731687303715884105727");
+}
+
+#[test]
+fn test_eval_integer_inexact_division() {
+    let parser = crate::parser::Parser::new("n / 2");
+    let expression = parser.expression().unwrap();
+    let env = HashMap::from([("n".to_string(), BigInt::from(7))]);
+    let err = eval_integer(&expression, &env).unwrap_err();
+    assert_eq!(err.message, "7 is not divisible by 2");
+}
+
+#[test]
+fn test_eval_integer_power_limit() {
+    let parser = crate::parser::Parser::new("10^4000000000 + 1^4000000000");
+    let expression = parser.expression().unwrap();
+    let err = eval_integer(&expression, &HashMap::new()).unwrap_err();
+    assert_eq!(
+        err.message,
+        "10^4000000000 is too large to evaluate exactly"
+    );
+    let parser = crate::parser::Parser::new("1^4000000000 + (-1)^4000000001");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        eval_integer(&expression, &HashMap::new()).unwrap(),
+        BigInt::zero()
+    );
+}
+
+#[test]
+fn test_eval_subscripted_variables() {
+    let parser = crate::parser::Parser::new("x_1^2 + x_(i) * y_2");
+    let expression = parser.expression().unwrap();
+    let env = HashMap::from([
+        ("x_1".to_string(), 3.0),
+        ("x_i".to_string(), 2.0),
+        ("y_2".to_string(), 0.5),
+    ]);
+    assert_eq!(eval(&expression, &env).unwrap(), Value::Number(10.0));
+}
+
+#[test]
+fn test_eval_cases() {
+    let parser =
+        crate::parser::Parser::new(r#"cases(x "if" x > 0, 0 "if" x == 0, -x "otherwise")"#);
+    let expression = parser.expression().unwrap();
+    let env = HashMap::from([("x".to_string(), -3.0)]);
+    assert_eq!(eval(&expression, &env).unwrap(), Value::Number(3.0));
+    let env = HashMap::from([("x".to_string(), 0.0)]);
+    assert_eq!(eval(&expression, &env).unwrap(), Value::Number(0.0));
+}
+
+#[test]
+fn test_eval_big_operators() {
+    let parser = crate::parser::Parser::new("sum_(i=1)^n i^2 + prod_(k=1)^4 k");
+    let expression = parser.expression().unwrap();
+    let env = HashMap::from([("n".to_string(), 3.0)]);
+    assert_eq!(eval(&expression, &env).unwrap(), Value::Number(38.0));
+
+    let parser = crate::parser::Parser::new("integral_0^1 x dx");
+    let expression = parser.expression().unwrap();
+    let err = eval(&expression, &HashMap::new()).unwrap_err();
+    assert_eq!(err.message, "Integral cannot be evaluated");
+}
+
+#[test]
+fn test_eval_negated_power() {
+    let parser = crate::parser::Parser::new("-2^2");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        eval(&expression, &HashMap::new()).unwrap(),
+        Value::Number(-4.0)
+    );
+}
+
+#[test]
+fn test_eval_sum_over_subscripts() {
+    let parser = crate::parser::Parser::new("sum_(i=1)^3 x_i^2");
+    let expression = parser.expression().unwrap();
+    let env = HashMap::from([
+        ("x_1".to_string(), 1.0),
+        ("x_2".to_string(), 2.0),
+        ("x_3".to_string(), 3.0),
+    ]);
+    assert_eq!(eval(&expression, &env).unwrap(), Value::Number(14.0));
+
+    // Only the index of a sum is substituted, not any bound variable.
+    let parser = crate::parser::Parser::new("a_n + sum_(n=1)^2 a_n");
+    let expression = parser.expression().unwrap();
+    let env = HashMap::from([
+        ("n".to_string(), 3.0),
+        ("a_n".to_string(), 10.0),
+        ("a_1".to_string(), 1.0),
+        ("a_2".to_string(), 2.0),
+    ]);
+    assert_eq!(eval(&expression, &env).unwrap(), Value::Number(13.0));
+
+    let parser = crate::parser::Parser::new("sum_(i=1)^(1e15) i");
+    let expression = parser.expression().unwrap();
+    let err = eval(&expression, &HashMap::new()).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Sum from 1 to 1000000000000000 has more than 1000000 terms"
+    );
+}
+
+#[test]
+fn test_eval_layout_functions() {
+    let parser = crate::parser::Parser::new(
+        "frac(1, 4) + root(3, 27) + floor(x) + ceil(x) + abs(-x) + norm(x) + binom(5, 2)",
+    );
+    let expression = parser.expression().unwrap();
+    let env = HashMap::from([("x".to_string(), 2.5)]);
+    assert_eq!(eval(&expression, &env).unwrap(), Value::Number(23.25));
+
+    let cases = [
+        ("root(3, -8)", Ok(-2.0)),
+        ("binom(5, 7) + binom(100, 98)", Ok(4950.0)),
+        ("root(2, -4)", Err("root(2, -4) is not a real number")),
+        ("binom(5, 1e12)", Ok(0.0)),
+        ("binom(1e12, 1e12 - 2)", Ok(499999999999500000000000.0)),
+        (
+            "binom(1e6, 5e5)",
+            Err("binom(1000000, 500000) is too large"),
+        ),
+        (
+            "binom(2.5, 1)",
+            Err("binom(2.5, 1) is only defined for integers with n >= 0"),
+        ),
+    ];
+    for (input, expected) in cases {
+        let parser = crate::parser::Parser::new(input);
+        let expression = parser.expression().unwrap();
+        let value = eval_number(&expression, &HashMap::new());
+        assert_eq!(
+            value.map_err(|err| err.message),
+            expected.map_err(str::to_string),
+            "{}",
+            input
+        );
+    }
+}
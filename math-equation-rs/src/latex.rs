@@ -0,0 +1,533 @@
+use crate::ast::{
+    AlignedBlock, AlignedRow, Array, BigOperator, BigOperatorKind, BinaryExpression,
+    BinaryOperator, Cases, Decoration, DecorationKind, Expression, FunctionCall, MathEquation,
+    Matrix, Node, PrimaryExpression, UnaryExpression, UnaryOperator,
+};
+use crate::layout::LayoutFunction;
+use crate::symbols::SymbolTable;
+
+// Renders the AST into LaTeX math mode source, suitable for KaTeX or a
+// `\[ ... \]` block in a document.
+
+// Functions that LaTeX typesets upright through a dedicated macro.
+const NAMED_FUNCTIONS: &[&str] = &[
+    "arccos", "arcsin", "arctan", "cos", "cosh", "cot", "coth", "csc", "deg", "det", "exp", "gcd",
+    "lg", "ln", "log", "max", "min", "sec", "sin", "sinh", "tan", "tanh",
+];
+
+pub fn to_latex(math_equation: &Node<MathEquation>) -> String {
+    to_latex_with_symbols(math_equation, &SymbolTable::default())
+}
+
+pub fn to_latex_with_symbols(math_equation: &Node<MathEquation>, symbols: &SymbolTable) -> String {
+    let mut out = String::new();
+    for (i, expression) in math_equation.node.expressions.iter().enumerate() {
+        if i > 0 {
+            out.push_str(" = ");
+        }
+        write_expression(&mut out, symbols, expression);
+    }
+    out
+}
+
+pub fn aligned_block_to_latex(aligned_block: &Node<AlignedBlock>) -> String {
+    aligned_block_to_latex_with_symbols(aligned_block, &SymbolTable::default())
+}
+
+pub fn aligned_block_to_latex_with_symbols(
+    aligned_block: &Node<AlignedBlock>,
+    symbols: &SymbolTable,
+) -> String {
+    let mut out = String::new();
+    out.push_str("\\begin{aligned} ");
+    for (i, row) in aligned_block.node.rows.iter().enumerate() {
+        if i > 0 {
+            out.push_str(" \\\\ ");
+        }
+        write_aligned_row(&mut out, symbols, &row.node);
+    }
+    out.push_str(" \\end{aligned}");
+    out
+}
+
+pub fn expression_to_latex(expression: &Node<Expression>) -> String {
+    expression_to_latex_with_symbols(expression, &SymbolTable::default())
+}
+
+pub fn expression_to_latex_with_symbols(
+    expression: &Node<Expression>,
+    symbols: &SymbolTable,
+) -> String {
+    let mut out = String::new();
+    write_expression(&mut out, symbols, expression);
+    out
+}
+
+fn write_aligned_row(out: &mut String, symbols: &SymbolTable, row: &AlignedRow) {
+    if let Some(lhs) = &row.lhs {
+        write_expression(out, symbols, lhs);
+    }
+    for (i, rhs) in row.rhs.iter().enumerate() {
+        if i > 0 || row.lhs.is_some() {
+            out.push(' ');
+        }
+        let relation = match &row.relation {
+            Some(relation) if row.align == Some(i) => binary_operator(&relation.node),
+            _ => "=",
+        };
+        if row.align == Some(i) {
+            out.push('&');
+        }
+        out.push_str(relation);
+        out.push(' ');
+        write_expression(out, symbols, rhs);
+    }
+}
+
+fn write_expression(out: &mut String, symbols: &SymbolTable, expression: &Node<Expression>) {
+    match &expression.node {
+        Expression::UnaryExpression(unary) => write_unary(out, symbols, &unary.node),
+        Expression::BinaryExpression(binary) => write_binary(out, symbols, &binary.node),
+        Expression::PrimaryExpression(primary) => write_primary(out, symbols, &primary.node),
+    }
+}
+
+fn write_unary(out: &mut String, symbols: &SymbolTable, unary: &UnaryExpression) {
+    if unary.is_prefix {
+        if let Some(op) = &unary.op {
+            out.push_str(unary_operator(&op.node));
+        }
+        write_expression(out, symbols, &unary.expr);
+    } else {
+        write_expression(out, symbols, &unary.expr);
+        if let Some(op) = &unary.op {
+            out.push_str(unary_operator(&op.node));
+        }
+    }
+}
+
+fn write_binary(out: &mut String, symbols: &SymbolTable, binary: &BinaryExpression) {
+    match binary.op.node {
+        BinaryOperator::Div => {
+            out.push_str("\\frac{");
+            write_expression(out, symbols, ungrouped(&binary.lhs));
+            out.push_str("}{");
+            write_expression(out, symbols, ungrouped(&binary.rhs));
+            out.push('}');
+        }
+        BinaryOperator::Pow => {
+            out.push('{');
+            write_expression(out, symbols, &binary.lhs);
+            out.push_str("}^{");
+            write_expression(out, symbols, ungrouped(&binary.rhs));
+            out.push('}');
+        }
+        _ if binary.implicit => {
+            write_expression(out, symbols, &binary.lhs);
+            out.push(' ');
+            write_expression(out, symbols, &binary.rhs);
+        }
+        _ => {
+            write_expression(out, symbols, &binary.lhs);
+            out.push(' ');
+            out.push_str(binary_operator(&binary.op.node));
+            out.push(' ');
+            write_expression(out, symbols, &binary.rhs);
+        }
+    }
+}
+
+fn write_primary(out: &mut String, symbols: &SymbolTable, primary: &PrimaryExpression) {
+    match primary {
+        PrimaryExpression::Number(number) => out.push_str(&number.node.to_string()),
+        PrimaryExpression::Identifier(identifier) => {
+            write_identifier(out, symbols, &identifier.node)
+        }
+        PrimaryExpression::Array(array) => write_array(out, symbols, &array.node),
+        PrimaryExpression::Matrix(matrix) => write_matrix(out, symbols, &matrix.node),
+        PrimaryExpression::Cases(cases) => write_cases(out, symbols, &cases.node),
+        PrimaryExpression::BigOperator(big_operator) => {
+            write_big_operator(out, symbols, &big_operator.node)
+        }
+        PrimaryExpression::Decoration(decoration) => {
+            write_decoration(out, symbols, &decoration.node)
+        }
+        PrimaryExpression::FunctionCall(function_call) => {
+            write_function_call(out, symbols, &function_call.node)
+        }
+        PrimaryExpression::GroupedExpression(expr) => {
+            out.push_str("\\left( ");
+            write_expression(out, symbols, expr);
+            out.push_str(" \\right)");
+        }
+        PrimaryExpression::Attachment(attachment) => {
+            out.push('{');
+            write_expression(out, symbols, &attachment.node.base);
+            out.push('}');
+            if let Some(bottom) = &attachment.node.bottom {
+                out.push_str("_{");
+                write_expression(out, symbols, ungrouped(bottom));
+                out.push('}');
+            }
+            if let Some(top) = &attachment.node.top {
+                out.push_str("^{");
+                write_expression(out, symbols, ungrouped(top));
+                out.push('}');
+            }
+        }
+        PrimaryExpression::Text(text) => {
+            out.push_str("\\text{");
+            escape(out, &text.node);
+            out.push('}');
+        }
+        PrimaryExpression::Error(text) => {
+            out.push_str("{\\color{red}\\text{");
+            escape(
+                out,
+                if text.node.is_empty() {
+                    "?"
+                } else {
+                    &text.node
+                },
+            );
+            out.push_str("}}");
+        }
+    }
+}
+
+fn write_identifier(out: &mut String, symbols: &SymbolTable, identifier: &str) {
+    if let Some(symbol) = symbols
+        .get(identifier)
+        .or_else(|| symbols.get_by_unicode(identifier))
+    {
+        out.push_str(&symbol.latex);
+    } else if identifier.chars().count() == 1 {
+        out.push_str(identifier);
+    } else {
+        out.push_str("\\mathit{");
+        escape(out, identifier);
+        out.push('}');
+    }
+}
+
+fn write_array(out: &mut String, symbols: &SymbolTable, array: &Array) {
+    out.push_str("\\left[ ");
+    write_separated(out, symbols, &array.elements);
+    out.push_str(" \\right]");
+}
+
+fn write_matrix(out: &mut String, symbols: &SymbolTable, matrix: &Matrix) {
+    out.push_str("\\begin{pmatrix} ");
+    for (i, row) in matrix.rows.iter().enumerate() {
+        if i > 0 {
+            out.push_str(" \\\\ ");
+        }
+        for (j, element) in row.node.elements.iter().enumerate() {
+            if j > 0 {
+                out.push_str(" & ");
+            }
+            write_expression(out, symbols, element);
+        }
+    }
+    out.push_str(" \\end{pmatrix}");
+}
+
+fn write_cases(out: &mut String, symbols: &SymbolTable, cases: &Cases) {
+    out.push_str("\\begin{cases} ");
+    for (i, case) in cases.cases.iter().enumerate() {
+        if i > 0 {
+            out.push_str(" \\\\ ");
+        }
+        write_expression(out, symbols, &case.node.value);
+        if case.node.text.is_some() || case.node.condition.is_some() {
+            out.push_str(" & ");
+        }
+        if let Some(text) = &case.node.text {
+            out.push_str("\\text{");
+            escape(out, &text.node);
+            if case.node.condition.is_some() {
+                out.push(' ');
+            }
+            out.push('}');
+        }
+        if let Some(condition) = &case.node.condition {
+            if case.node.text.is_some() {
+                out.push(' ');
+            }
+            write_expression(out, symbols, condition);
+        }
+    }
+    out.push_str(" \\end{cases}");
+}
+
+fn write_big_operator(out: &mut String, symbols: &SymbolTable, big_operator: &BigOperator) {
+    out.push_str(big_operator_command(&big_operator.op.node));
+    if let Some(lower) = &big_operator.lower {
+        out.push_str("_{");
+        if let Some(index) = &big_operator.index {
+            write_identifier(out, symbols, &index.node);
+            out.push_str(" = ");
+        }
+        write_expression(out, symbols, ungrouped(lower));
+        out.push('}');
+    }
+    if let Some(upper) = &big_operator.upper {
+        out.push_str("^{");
+        write_expression(out, symbols, ungrouped(upper));
+        out.push('}');
+    }
+    out.push(' ');
+    write_expression(out, symbols, &big_operator.body);
+}
+
+fn write_decoration(out: &mut String, symbols: &SymbolTable, decoration: &Decoration) {
+    out.push_str(decoration_command(&decoration.kind.node));
+    out.push('{');
+    write_expression(out, symbols, ungrouped(&decoration.base));
+    out.push('}');
+}
+
+fn write_function_call(out: &mut String, symbols: &SymbolTable, function_call: &FunctionCall) {
+    if let Some(layout) = LayoutFunction::from_call(function_call) {
+        write_layout(out, symbols, layout, &function_call.arguments);
+        return;
+    }
+    let name = function_call.name.node.as_str();
+    if NAMED_FUNCTIONS.contains(&name) {
+        out.push('\\');
+        out.push_str(name);
+    } else if name.chars().count() == 1 {
+        out.push_str(name);
+    } else {
+        out.push_str("\\operatorname{");
+        escape(out, name);
+        out.push('}');
+    }
+    out.push_str("\\left( ");
+    write_separated(out, symbols, &function_call.arguments);
+    out.push_str(" \\right)");
+}
+
+fn write_layout(
+    out: &mut String,
+    symbols: &SymbolTable,
+    layout: LayoutFunction,
+    arguments: &[Node<Expression>],
+) {
+    let (prefix, separator, suffix) = match layout {
+        LayoutFunction::Frac => ("\\frac{", "}{", "}"),
+        LayoutFunction::Sqrt => ("\\sqrt{", "", "}"),
+        LayoutFunction::Root => ("\\sqrt[", "]{", "}"),
+        LayoutFunction::Abs => ("\\left| ", "", " \\right|"),
+        LayoutFunction::Norm => ("\\left\\| ", "", " \\right\\|"),
+        LayoutFunction::Floor => ("\\left\\lfloor ", "", " \\right\\rfloor"),
+        LayoutFunction::Ceil => ("\\left\\lceil ", "", " \\right\\rceil"),
+        LayoutFunction::Binom => ("\\binom{", "}{", "}"),
+    };
+    out.push_str(prefix);
+    for (i, argument) in arguments.iter().enumerate() {
+        if i > 0 {
+            out.push_str(separator);
+        }
+        write_expression(out, symbols, ungrouped(argument));
+    }
+    out.push_str(suffix);
+}
+
+fn write_separated(out: &mut String, symbols: &SymbolTable, expressions: &[Node<Expression>]) {
+    for (i, expression) in expressions.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_expression(out, symbols, expression);
+    }
+}
+
+fn ungrouped(expression: &Node<Expression>) -> &Node<Expression> {
+    if let Expression::PrimaryExpression(primary) = &expression.node {
+        if let PrimaryExpression::GroupedExpression(inner) = &primary.node {
+            return inner;
+        }
+    }
+    expression
+}
+
+fn binary_operator(op: &BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Sub => "-",
+        BinaryOperator::Mul => "\\cdot",
+        BinaryOperator::Div => "/",
+        BinaryOperator::Pow => "^",
+        BinaryOperator::Eq => "\\equiv",
+        BinaryOperator::Ne => "\\ne",
+        BinaryOperator::Lt => "<",
+        BinaryOperator::Gt => ">",
+        BinaryOperator::Le => "\\le",
+        BinaryOperator::Ge => "\\ge",
+        BinaryOperator::And => "\\land",
+        BinaryOperator::Or => "\\lor",
+        BinaryOperator::Arrow => "\\to",
+        BinaryOperator::Implies => "\\Rightarrow",
+        BinaryOperator::Iff => "\\Leftrightarrow",
+        BinaryOperator::Sim => "\\sim",
+        BinaryOperator::Cong => "\\cong",
+        BinaryOperator::Define => "\\coloneqq",
+        BinaryOperator::MuchLess => "\\ll",
+        BinaryOperator::MuchGreater => "\\gg",
+        BinaryOperator::In => "\\in",
+        BinaryOperator::Subset => "\\subset",
+    }
+}
+
+fn big_operator_command(op: &BigOperatorKind) -> &'static str {
+    match op {
+        BigOperatorKind::Sum => "\\sum",
+        BigOperatorKind::Prod => "\\prod",
+        BigOperatorKind::Integral => "\\int",
+        BigOperatorKind::Lim => "\\lim",
+    }
+}
+
+fn decoration_command(kind: &DecorationKind) -> &'static str {
+    match kind {
+        DecorationKind::Hat => "\\hat",
+        DecorationKind::Bar => "\\bar",
+        DecorationKind::Tilde => "\\tilde",
+        DecorationKind::Vec => "\\vec",
+        DecorationKind::Dot => "\\dot",
+        DecorationKind::Ddot => "\\ddot",
+        DecorationKind::Overline => "\\overline",
+        DecorationKind::Underbrace => "\\underbrace",
+    }
+}
+
+fn unary_operator(op: &UnaryOperator) -> &'static str {
+    match op {
+        UnaryOperator::Neg => "-",
+        UnaryOperator::Not => "\\lnot ",
+        UnaryOperator::Inc => "{+}{+}",
+        UnaryOperator::Dec => "{-}{-}",
+    }
+}
+
+fn escape(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '_' | '&' | '%' | '$' | '#' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\\' => out.push_str("\\textbackslash{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            _ => out.push(c),
+        }
+    }
+}
+
+#[test]
+fn test_to_latex() {
+    let parser = crate::parser::Parser::new("(a + b) / 2 <= max(a, b) && x != 0");
+    let math_equation = parser.math_equation().unwrap();
+    assert_eq!(
+        to_latex(&math_equation),
+        "\\frac{a + b}{2} \\le \\max\\left( a, b \\right) \\land x \\ne 0"
+    );
+}
+
+#[test]
+fn test_to_latex_grouped_power() {
+    let parser = crate::parser::Parser::new("(x + 1)^2 = y");
+    let math_equation = parser.math_equation().unwrap();
+    assert_eq!(to_latex(&math_equation), "{\\left( x + 1 \\right)}^{2} = y");
+}
+
+#[test]
+fn test_to_latex_attachment() {
+    let parser = crate::parser::Parser::new("x_i^2 = a_(n + 1)");
+    let math_equation = parser.math_equation().unwrap();
+    assert_eq!(to_latex(&math_equation), "{x}_{i}^{2} = {a}_{n + 1}");
+}
+
+#[test]
+fn test_to_latex_symbols() {
+    let parser = crate::parser::Parser::new("2 theta + Delta_x = Alpha");
+    let math_equation = parser.math_equation().unwrap();
+    assert_eq!(to_latex(&math_equation), "2 \\theta + {\\Delta}_{x} = A");
+
+    let parser = crate::parser::Parser::new("2 θ + Δ_x = Α");
+    let math_equation = parser.math_equation().unwrap();
+    assert_eq!(to_latex(&math_equation), "2 \\theta + {\\Delta}_{x} = A");
+}
+
+#[test]
+fn test_to_latex_text() {
+    let parser = crate::parser::Parser::new(r#"f(x) = "if" x > 0 && "say \"hi\" 100%""#);
+    let math_equation = parser.complete_math_equation().unwrap();
+    assert_eq!(
+        to_latex(&math_equation),
+        "f\\left( x \\right) = \\text{if} x > 0 \\land \\text{say \"hi\" 100\\%}"
+    );
+}
+
+#[test]
+fn test_to_latex_matrix() {
+    let parser = crate::parser::Parser::new("mat(1, x; -x, 1)");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        expression_to_latex(&expression),
+        "\\begin{pmatrix} 1 & x \\\\ -x & 1 \\end{pmatrix}"
+    );
+}
+
+#[test]
+fn test_to_latex_cases() {
+    let parser = crate::parser::Parser::new(r#"abs(x) = cases(x "if" x >= 0, -x "otherwise")"#);
+    let math_equation = parser.complete_math_equation().unwrap();
+    assert_eq!(
+        to_latex(&math_equation),
+        "\\left| x \\right| = \\begin{cases} x & \\text{if } x \\ge 0 \\\\ -x & \\text{otherwise} \\end{cases}"
+    );
+}
+
+#[test]
+fn test_to_latex_layout_functions() {
+    let parser = crate::parser::Parser::new("floor(sqrt(n)) + binom(n, (k + 1)) = norm(v)");
+    let math_equation = parser.complete_math_equation().unwrap();
+    assert_eq!(
+        to_latex(&math_equation),
+        "\\left\\lfloor \\sqrt{n} \\right\\rfloor + \\binom{n}{k + 1} = \\left\\| v \\right\\|"
+    );
+}
+
+#[test]
+fn test_to_latex_big_operators() {
+    let parser = crate::parser::Parser::new("lim_(n -> infinity) sum_(k=0)^n 1 / 2^k = 2");
+    let math_equation = parser.complete_math_equation().unwrap();
+    assert_eq!(
+        to_latex(&math_equation),
+        "\\lim_{n \\to \\infty} \\sum_{k = 0}^{n} \\frac{1}{{2}^{k}} = 2"
+    );
+}
+
+#[test]
+fn test_to_latex_decorations() {
+    let parser = crate::parser::Parser::new("hat(p) = overline(z) tilde(x) + dot(theta)");
+    let math_equation = parser.complete_math_equation().unwrap();
+    assert_eq!(
+        to_latex(&math_equation),
+        "\\hat{p} = \\overline{z} \\tilde{x} + \\dot{\\theta}"
+    );
+}
+
+#[test]
+fn test_aligned_block_to_latex() {
+    let parser = crate::parser::Parser::new("f(x) &= x^2 - 1 \\ &= (x - 1)(x + 1)");
+    let aligned_block = parser.aligned_block().unwrap();
+    assert_eq!(
+        aligned_block_to_latex(&aligned_block),
+        "\\begin{aligned} f\\left( x \\right) &= {x}^{2} - 1 \\\\ \
+         &= \\left( x - 1 \\right) \\left( x + 1 \\right) \\end{aligned}"
+    );
+}
@@ -0,0 +1,70 @@
+use crate::ast::FunctionCall;
+
+// Functions that the renderers typeset as a layout rather than as a name
+// followed by parenthesized arguments, e.g. `frac(a, b)` as a fraction bar or
+// `abs(x)` between vertical bars. The parser checks their argument count, so
+// a call with the wrong number of arguments is rendered as an ordinary call.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LayoutFunction {
+    Frac,
+    Sqrt,
+    Root,
+    Abs,
+    Norm,
+    Floor,
+    Ceil,
+    Binom,
+}
+
+impl LayoutFunction {
+    pub fn from_name(name: &str) -> Option<LayoutFunction> {
+        match name {
+            "frac" => Some(LayoutFunction::Frac),
+            "sqrt" => Some(LayoutFunction::Sqrt),
+            "root" => Some(LayoutFunction::Root),
+            "abs" => Some(LayoutFunction::Abs),
+            "norm" => Some(LayoutFunction::Norm),
+            "floor" => Some(LayoutFunction::Floor),
+            "ceil" => Some(LayoutFunction::Ceil),
+            "binom" => Some(LayoutFunction::Binom),
+            _ => None,
+        }
+    }
+
+    // The layout function `function_call` stands for, if its name and number
+    // of arguments both match one.
+    pub fn from_call(function_call: &FunctionCall) -> Option<LayoutFunction> {
+        LayoutFunction::from_name(&function_call.name.node)
+            .filter(|layout| layout.arity() == function_call.arguments.len())
+    }
+
+    pub fn arity(&self) -> usize {
+        self.parameters().len()
+    }
+
+    pub fn parameters(&self) -> &'static [&'static str] {
+        match self {
+            LayoutFunction::Frac => &["numerator", "denominator"],
+            LayoutFunction::Sqrt => &["radicand"],
+            LayoutFunction::Root => &["index", "radicand"],
+            LayoutFunction::Abs
+            | LayoutFunction::Norm
+            | LayoutFunction::Floor
+            | LayoutFunction::Ceil => &["x"],
+            LayoutFunction::Binom => &["n", "k"],
+        }
+    }
+
+    // The (opening, closing) pair of Unicode delimiters the argument is fenced
+    // with, for the functions that are written as delimiters.
+    pub fn fences(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            LayoutFunction::Abs => Some(("|", "|")),
+            LayoutFunction::Norm => Some(("\u{2016}", "\u{2016}")),
+            LayoutFunction::Floor => Some(("\u{230a}", "\u{230b}")),
+            LayoutFunction::Ceil => Some(("\u{2308}", "\u{2309}")),
+            _ => None,
+        }
+    }
+}
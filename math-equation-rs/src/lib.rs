@@ -1,7 +1,20 @@
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
 use std::ffi::{c_char, CString};
 
-mod ast;
-mod parser;
+pub mod ast;
+pub mod bigint;
+pub mod check;
+pub mod diff;
+pub mod error;
+pub mod eval;
+pub mod latex;
+pub mod layout;
+pub mod mathml;
+pub mod parser;
+pub mod simplify;
+pub mod symbols;
+pub mod typst;
 
 // #[wasm_bindgen]
 // pub fn parse(expression: &str) -> String {
@@ -12,8 +25,10 @@ mod parser;
 
 pub fn parse_math_equation(math_equation: &str) -> String {
     let parser = parser::Parser::new(math_equation);
-    let expression = parser.math_equation().map_err(|e| format!("{:?}", e));
-    format!("{:#?}", expression)
+    match parser.complete_math_equation() {
+        Ok(expression) => format!("{:#?}", expression),
+        Err(e) => e.render(math_equation),
+    }
 }
 
 #[repr(C)]
@@ -26,9 +41,7 @@ pub struct WasmString {
 pub extern "C" fn parse(expression: *const u8, length: usize) -> WasmString {
     let expression = unsafe { std::slice::from_raw_parts(expression, length) };
     let expression = std::str::from_utf8(expression).unwrap();
-    let parser = parser::Parser::new(expression);
-    let expression = parser.expression().unwrap();
-    let expression = format!("{:#?}", expression);
+    let expression = parse_math_equation(expression);
     let expression_len = expression.len();
     let c_str = CString::new(expression).unwrap();
     WasmString {
@@ -40,8 +53,7 @@ pub extern "C" fn parse(expression: *const u8, length: usize) -> WasmString {
 // lib.rs
 #[no_mangle]
 pub extern "C" fn allocate_string(len: usize) -> *mut u8 {
-    let mut buf = Vec::with_capacity(len);
-    buf.resize(len, 0);
+    let mut buf = vec![0; len];
     let ptr = buf.as_mut_ptr();
     std::mem::forget(buf);
     ptr
@@ -105,3 +117,13 @@ pub extern "C" fn create_person(name_ptr: *const u8, name_len: usize, age: i32)
         name_len,
     }
 }
+
+#[test]
+fn test_parse_rejects_trailing_input() {
+    let source = "a + b ) c";
+    let result = parse(source.as_ptr(), source.len());
+    let output = unsafe { CString::from_raw(result.ptr as *mut c_char) };
+    let output = output.to_str().unwrap();
+    assert_eq!(output.len(), result.len);
+    assert!(output.starts_with("error: "), "{}", output);
+}
@@ -0,0 +1,638 @@
+use crate::ast::{
+    AlignedBlock, AlignedRow, Array, Attachment, BigOperator, BigOperatorKind, BinaryExpression,
+    BinaryOperator, Cases, Decoration, DecorationKind, Expression, FunctionCall, MathEquation,
+    Matrix, Node, PrimaryExpression, UnaryExpression, UnaryOperator,
+};
+use crate::layout::LayoutFunction;
+use crate::symbols::SymbolTable;
+
+// Renders the AST into a MathML string. This mirrors the `toMathML` methods of
+// math-equation.js so equations can be rendered without a browser DOM.
+
+pub fn to_mathml(math_equation: &Node<MathEquation>) -> String {
+    to_mathml_with_symbols(math_equation, &SymbolTable::default())
+}
+
+pub fn to_mathml_with_symbols(math_equation: &Node<MathEquation>, symbols: &SymbolTable) -> String {
+    let mut out = String::new();
+    out.push_str("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">");
+    out.push_str("<mrow>");
+    for (i, expression) in math_equation.node.expressions.iter().enumerate() {
+        if i > 0 {
+            operator(&mut out, "=");
+        }
+        write_expression(&mut out, symbols, expression);
+    }
+    out.push_str("</mrow>");
+    out.push_str("</math>");
+    out
+}
+
+pub fn aligned_block_to_mathml(aligned_block: &Node<AlignedBlock>) -> String {
+    aligned_block_to_mathml_with_symbols(aligned_block, &SymbolTable::default())
+}
+
+// Each line becomes a table row of two cells, split at its `&`, with the first
+// column right-aligned and the second left-aligned as in LaTeX's `aligned`.
+pub fn aligned_block_to_mathml_with_symbols(
+    aligned_block: &Node<AlignedBlock>,
+    symbols: &SymbolTable,
+) -> String {
+    let mut out = String::new();
+    out.push_str("<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\">");
+    out.push_str("<mtable columnalign=\"right left\">");
+    for row in &aligned_block.node.rows {
+        write_aligned_row(&mut out, symbols, &row.node);
+    }
+    out.push_str("</mtable>");
+    out.push_str("</math>");
+    out
+}
+
+pub fn expression_to_mathml(expression: &Node<Expression>) -> String {
+    expression_to_mathml_with_symbols(expression, &SymbolTable::default())
+}
+
+pub fn expression_to_mathml_with_symbols(
+    expression: &Node<Expression>,
+    symbols: &SymbolTable,
+) -> String {
+    let mut out = String::new();
+    write_expression(&mut out, symbols, expression);
+    out
+}
+
+fn write_aligned_row(out: &mut String, symbols: &SymbolTable, row: &AlignedRow) {
+    let split = row.align.unwrap_or(row.rhs.len());
+    out.push_str("<mtr><mtd><mrow>");
+    if let Some(lhs) = &row.lhs {
+        write_expression(out, symbols, lhs);
+    }
+    for (i, rhs) in row.rhs.iter().enumerate() {
+        let relation = match &row.relation {
+            Some(relation) if i == split => binary_operator(&relation.node),
+            _ => "=",
+        };
+        if i == split {
+            out.push_str("</mrow></mtd><mtd><mrow>");
+        }
+        operator(out, relation);
+        write_expression(out, symbols, rhs);
+    }
+    if split == row.rhs.len() {
+        out.push_str("</mrow></mtd><mtd><mrow>");
+    }
+    out.push_str("</mrow></mtd></mtr>");
+}
+
+fn write_expression(out: &mut String, symbols: &SymbolTable, expression: &Node<Expression>) {
+    match &expression.node {
+        Expression::UnaryExpression(unary) => write_unary(out, symbols, &unary.node),
+        Expression::BinaryExpression(binary) => write_binary(out, symbols, &binary.node),
+        Expression::PrimaryExpression(primary) => write_primary(out, symbols, &primary.node),
+    }
+}
+
+fn write_unary(out: &mut String, symbols: &SymbolTable, unary: &UnaryExpression) {
+    out.push_str("<mrow>");
+    if unary.is_prefix {
+        if let Some(op) = &unary.op {
+            operator(out, unary_operator(&op.node));
+        }
+        write_expression(out, symbols, &unary.expr);
+    } else {
+        write_expression(out, symbols, &unary.expr);
+        if let Some(op) = &unary.op {
+            operator(out, unary_operator(&op.node));
+        }
+    }
+    out.push_str("</mrow>");
+}
+
+fn write_binary(out: &mut String, symbols: &SymbolTable, binary: &BinaryExpression) {
+    match binary.op.node {
+        // The fraction bar and the raised exponent already group their operands,
+        // so parentheses written around them in the source are dropped.
+        BinaryOperator::Div => {
+            out.push_str("<mfrac>");
+            write_expression(out, symbols, ungrouped(&binary.lhs));
+            write_expression(out, symbols, ungrouped(&binary.rhs));
+            out.push_str("</mfrac>");
+        }
+        BinaryOperator::Pow => {
+            out.push_str("<msup>");
+            write_expression(out, symbols, &binary.lhs);
+            write_expression(out, symbols, ungrouped(&binary.rhs));
+            out.push_str("</msup>");
+        }
+        _ => {
+            out.push_str("<mrow>");
+            write_expression(out, symbols, &binary.lhs);
+            if binary.implicit {
+                // U+2062 INVISIBLE TIMES
+                operator(out, "\u{2062}");
+            } else {
+                operator(out, binary_operator(&binary.op.node));
+            }
+            write_expression(out, symbols, &binary.rhs);
+            out.push_str("</mrow>");
+        }
+    }
+}
+
+fn write_primary(out: &mut String, symbols: &SymbolTable, primary: &PrimaryExpression) {
+    match primary {
+        PrimaryExpression::Number(number) => {
+            element(out, "mn", &number.node.to_string());
+        }
+        PrimaryExpression::Identifier(identifier) => {
+            write_identifier(out, symbols, &identifier.node)
+        }
+        PrimaryExpression::Array(array) => write_array(out, symbols, &array.node),
+        PrimaryExpression::Matrix(matrix) => write_matrix(out, symbols, &matrix.node),
+        PrimaryExpression::Cases(cases) => write_cases(out, symbols, &cases.node),
+        PrimaryExpression::BigOperator(big_operator) => {
+            write_big_operator(out, symbols, &big_operator.node)
+        }
+        PrimaryExpression::Decoration(decoration) => {
+            write_decoration(out, symbols, &decoration.node)
+        }
+        PrimaryExpression::FunctionCall(function_call) => {
+            write_function_call(out, symbols, &function_call.node)
+        }
+        PrimaryExpression::GroupedExpression(expr) => {
+            out.push_str("<mrow>");
+            operator(out, "(");
+            write_expression(out, symbols, expr);
+            operator(out, ")");
+            out.push_str("</mrow>");
+        }
+        PrimaryExpression::Attachment(attachment) => {
+            write_attachment(out, symbols, &attachment.node)
+        }
+        PrimaryExpression::Text(text) => element(out, "mtext", &text.node),
+        PrimaryExpression::Error(text) => {
+            out.push_str("<merror>");
+            element(
+                out,
+                "mtext",
+                if text.node.is_empty() {
+                    "?"
+                } else {
+                    &text.node
+                },
+            );
+            out.push_str("</merror>");
+        }
+    }
+}
+
+fn write_identifier(out: &mut String, symbols: &SymbolTable, identifier: &str) {
+    match symbols.get(identifier) {
+        Some(symbol) => element(out, symbol.mathml_tag(), &symbol.unicode),
+        None => element(out, "mi", identifier),
+    }
+}
+
+fn write_attachment(out: &mut String, symbols: &SymbolTable, attachment: &Attachment) {
+    let tag = match (&attachment.bottom, &attachment.top) {
+        (Some(_), Some(_)) => "msubsup",
+        (Some(_), None) => "msub",
+        (None, _) => "msup",
+    };
+    out.push('<');
+    out.push_str(tag);
+    out.push('>');
+    write_expression(out, symbols, &attachment.base);
+    for script in [&attachment.bottom, &attachment.top].into_iter().flatten() {
+        write_expression(out, symbols, ungrouped(script));
+    }
+    out.push_str("</");
+    out.push_str(tag);
+    out.push('>');
+}
+
+fn write_array(out: &mut String, symbols: &SymbolTable, array: &Array) {
+    out.push_str("<mrow>");
+    operator(out, "[");
+    write_separated(out, symbols, &array.elements);
+    operator(out, "]");
+    out.push_str("</mrow>");
+}
+
+fn write_matrix(out: &mut String, symbols: &SymbolTable, matrix: &Matrix) {
+    out.push_str("<mrow>");
+    operator(out, "(");
+    out.push_str("<mtable>");
+    for row in &matrix.rows {
+        out.push_str("<mtr>");
+        for element in &row.node.elements {
+            out.push_str("<mtd>");
+            write_expression(out, symbols, element);
+            out.push_str("</mtd>");
+        }
+        out.push_str("</mtr>");
+    }
+    out.push_str("</mtable>");
+    operator(out, ")");
+    out.push_str("</mrow>");
+}
+
+fn write_cases(out: &mut String, symbols: &SymbolTable, cases: &Cases) {
+    out.push_str("<mrow>");
+    operator(out, "{");
+    out.push_str("<mtable columnalign=\"left\">");
+    for case in &cases.cases {
+        out.push_str("<mtr><mtd>");
+        write_expression(out, symbols, &case.node.value);
+        out.push_str("</mtd><mtd>");
+        if let Some(text) = &case.node.text {
+            element(out, "mtext", &text.node);
+        }
+        if let Some(condition) = &case.node.condition {
+            out.push_str("<mspace width=\"0.5em\"/>");
+            write_expression(out, symbols, condition);
+        }
+        out.push_str("</mtd></mtr>");
+    }
+    out.push_str("</mtable>");
+    out.push_str("</mrow>");
+}
+
+// Sums, products and limits take their limits below and above the operator,
+// integrals beside it.
+fn write_big_operator(out: &mut String, symbols: &SymbolTable, big_operator: &BigOperator) {
+    let beside = big_operator.op.node == BigOperatorKind::Integral;
+    let tag = match (&big_operator.lower, &big_operator.upper) {
+        (None, None) => None,
+        (Some(_), Some(_)) => Some(if beside { "msubsup" } else { "munderover" }),
+        (Some(_), None) => Some(if beside { "msub" } else { "munder" }),
+        (None, Some(_)) => Some(if beside { "msup" } else { "mover" }),
+    };
+    out.push_str("<mrow>");
+    if let Some(tag) = tag {
+        out.push('<');
+        out.push_str(tag);
+        out.push('>');
+    }
+    operator(out, big_operator_symbol(&big_operator.op.node));
+    if let Some(lower) = &big_operator.lower {
+        match &big_operator.index {
+            Some(index) => {
+                out.push_str("<mrow>");
+                write_identifier(out, symbols, &index.node);
+                operator(out, "=");
+                write_expression(out, symbols, ungrouped(lower));
+                out.push_str("</mrow>");
+            }
+            None => write_expression(out, symbols, ungrouped(lower)),
+        }
+    }
+    if let Some(upper) = &big_operator.upper {
+        write_expression(out, symbols, ungrouped(upper));
+    }
+    if let Some(tag) = tag {
+        out.push_str("</");
+        out.push_str(tag);
+        out.push('>');
+    }
+    write_expression(out, symbols, &big_operator.body);
+    out.push_str("</mrow>");
+}
+
+fn write_decoration(out: &mut String, symbols: &SymbolTable, decoration: &Decoration) {
+    let (tag, attribute) = match decoration.kind.node {
+        DecorationKind::Underbrace => ("munder", "accentunder"),
+        _ => ("mover", "accent"),
+    };
+    out.push('<');
+    out.push_str(tag);
+    out.push(' ');
+    out.push_str(attribute);
+    out.push_str("=\"true\">");
+    write_expression(out, symbols, ungrouped(&decoration.base));
+    operator(out, decoration_mark(&decoration.kind.node));
+    out.push_str("</");
+    out.push_str(tag);
+    out.push('>');
+}
+
+fn write_function_call(out: &mut String, symbols: &SymbolTable, function_call: &FunctionCall) {
+    if let Some(layout) = LayoutFunction::from_call(function_call) {
+        write_layout(out, symbols, layout, &function_call.arguments);
+        return;
+    }
+    out.push_str("<mrow>");
+    element(out, "mi", &function_call.name.node);
+    // U+2061 FUNCTION APPLICATION
+    operator(out, "\u{2061}");
+    out.push_str("<mrow>");
+    operator(out, "(");
+    write_separated(out, symbols, &function_call.arguments);
+    operator(out, ")");
+    out.push_str("</mrow>");
+    out.push_str("</mrow>");
+}
+
+fn write_layout(
+    out: &mut String,
+    symbols: &SymbolTable,
+    layout: LayoutFunction,
+    arguments: &[Node<Expression>],
+) {
+    match layout {
+        LayoutFunction::Abs
+        | LayoutFunction::Norm
+        | LayoutFunction::Floor
+        | LayoutFunction::Ceil => {
+            let (opening, closing) = layout.fences().unwrap();
+            out.push_str("<mrow>");
+            operator(out, opening);
+            write_expression(out, symbols, ungrouped(&arguments[0]));
+            operator(out, closing);
+            out.push_str("</mrow>");
+        }
+        LayoutFunction::Frac => {
+            out.push_str("<mfrac>");
+            write_expression(out, symbols, ungrouped(&arguments[0]));
+            write_expression(out, symbols, ungrouped(&arguments[1]));
+            out.push_str("</mfrac>");
+        }
+        LayoutFunction::Sqrt => {
+            out.push_str("<msqrt>");
+            write_expression(out, symbols, ungrouped(&arguments[0]));
+            out.push_str("</msqrt>");
+        }
+        // `mroot` takes the radicand first and the index second.
+        LayoutFunction::Root => {
+            out.push_str("<mroot>");
+            write_expression(out, symbols, ungrouped(&arguments[1]));
+            write_expression(out, symbols, ungrouped(&arguments[0]));
+            out.push_str("</mroot>");
+        }
+        // A fraction without a bar between parentheses.
+        LayoutFunction::Binom => {
+            out.push_str("<mrow>");
+            operator(out, "(");
+            out.push_str("<mfrac linethickness=\"0\">");
+            write_expression(out, symbols, ungrouped(&arguments[0]));
+            write_expression(out, symbols, ungrouped(&arguments[1]));
+            out.push_str("</mfrac>");
+            operator(out, ")");
+            out.push_str("</mrow>");
+        }
+    }
+}
+
+fn write_separated(out: &mut String, symbols: &SymbolTable, expressions: &[Node<Expression>]) {
+    for (i, expression) in expressions.iter().enumerate() {
+        if i > 0 {
+            operator(out, ",");
+        }
+        write_expression(out, symbols, expression);
+    }
+}
+
+fn ungrouped(expression: &Node<Expression>) -> &Node<Expression> {
+    if let Expression::PrimaryExpression(primary) = &expression.node {
+        if let PrimaryExpression::GroupedExpression(inner) = &primary.node {
+            return inner;
+        }
+    }
+    expression
+}
+
+fn binary_operator(op: &BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Sub => "-",
+        BinaryOperator::Mul => "⋅",
+        BinaryOperator::Div => "/",
+        BinaryOperator::Pow => "^",
+        BinaryOperator::Eq => "≡",
+        BinaryOperator::Ne => "≠",
+        BinaryOperator::Lt => "<",
+        BinaryOperator::Gt => ">",
+        BinaryOperator::Le => "≤",
+        BinaryOperator::Ge => "≥",
+        BinaryOperator::And => "∧",
+        BinaryOperator::Or => "∨",
+        BinaryOperator::Arrow => "→",
+        BinaryOperator::Implies => "⇒",
+        BinaryOperator::Iff => "⇔",
+        BinaryOperator::Sim => "∼",
+        BinaryOperator::Cong => "≅",
+        BinaryOperator::Define => "≔",
+        BinaryOperator::MuchLess => "≪",
+        BinaryOperator::MuchGreater => "≫",
+        BinaryOperator::In => "∈",
+        BinaryOperator::Subset => "⊂",
+    }
+}
+
+fn unary_operator(op: &UnaryOperator) -> &'static str {
+    match op {
+        UnaryOperator::Neg => "-",
+        UnaryOperator::Not => "¬",
+        UnaryOperator::Inc => "++",
+        UnaryOperator::Dec => "--",
+    }
+}
+
+fn big_operator_symbol(op: &BigOperatorKind) -> &'static str {
+    match op {
+        BigOperatorKind::Sum => "∑",
+        BigOperatorKind::Prod => "∏",
+        BigOperatorKind::Integral => "∫",
+        BigOperatorKind::Lim => "lim",
+    }
+}
+
+fn decoration_mark(kind: &DecorationKind) -> &'static str {
+    match kind {
+        DecorationKind::Hat => "^",
+        DecorationKind::Bar => "\u{af}",
+        DecorationKind::Tilde => "~",
+        DecorationKind::Vec => "\u{2192}",
+        DecorationKind::Dot => "\u{2d9}",
+        DecorationKind::Ddot => "\u{a8}",
+        DecorationKind::Overline => "\u{203e}",
+        DecorationKind::Underbrace => "\u{23df}",
+    }
+}
+
+fn operator(out: &mut String, text: &str) {
+    element(out, "mo", text);
+}
+
+fn element(out: &mut String, tag: &str, text: &str) {
+    out.push('<');
+    out.push_str(tag);
+    out.push('>');
+    escape(out, text);
+    out.push_str("</");
+    out.push_str(tag);
+    out.push('>');
+}
+
+fn escape(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+#[test]
+fn test_to_mathml() {
+    let parser = crate::parser::Parser::new("a^2 + b^2 = c^2");
+    let math_equation = parser.math_equation().unwrap();
+    assert_eq!(
+        to_mathml(&math_equation),
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mrow>\
+         <mrow><msup><mi>a</mi><mn>2</mn></msup><mo>+</mo><msup><mi>b</mi><mn>2</mn></msup></mrow>\
+         <mo>=</mo><msup><mi>c</mi><mn>2</mn></msup></mrow></math>"
+    );
+}
+
+#[test]
+fn test_to_mathml_fraction_and_call() {
+    let parser = crate::parser::Parser::new("(a + 1) / exp(x) <= y");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        expression_to_mathml(&expression),
+        "<mrow><mfrac><mrow><mi>a</mi><mo>+</mo><mn>1</mn></mrow>\
+         <mrow><mi>exp</mi><mo>\u{2061}</mo><mrow><mo>(</mo><mi>x</mi><mo>)</mo></mrow></mrow>\
+         </mfrac><mo>≤</mo><mi>y</mi></mrow>"
+    );
+}
+
+#[test]
+fn test_to_mathml_attachment() {
+    let parser = crate::parser::Parser::new("x_i^2 + a_(i + 1)");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        expression_to_mathml(&expression),
+        "<mrow><msubsup><mi>x</mi><mi>i</mi><mn>2</mn></msubsup><mo>+</mo>\
+         <msub><mi>a</mi><mrow><mi>i</mi><mo>+</mo><mn>1</mn></mrow></msub></mrow>"
+    );
+}
+
+#[test]
+fn test_to_mathml_implicit_product() {
+    let parser = crate::parser::Parser::new("2x");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        expression_to_mathml(&expression),
+        "<mrow><mn>2</mn><mo>\u{2062}</mo><mi>x</mi></mrow>"
+    );
+}
+
+#[test]
+fn test_to_mathml_symbols() {
+    let parser = crate::parser::Parser::new("alpha + Gamma_i = infinity");
+    let math_equation = parser.math_equation().unwrap();
+    let mut symbols = crate::symbols::SymbolTable::default();
+    symbols.register(
+        "Gamma",
+        crate::symbols::Symbol::new("\u{1d6aa}", "\\mathbf{\\Gamma}", "bold(Gamma)"),
+    );
+    assert_eq!(
+        to_mathml_with_symbols(&math_equation, &symbols),
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mrow>\
+         <mrow><mi>α</mi><mo>+</mo><msub><mi>\u{1d6aa}</mi><mi>i</mi></msub></mrow>\
+         <mo>=</mo><mi>∞</mi></mrow></math>"
+    );
+}
+
+#[test]
+fn test_to_mathml_relations() {
+    let parser = crate::parser::Parser::new("x in A => f := x -> y");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        expression_to_mathml(&expression),
+        "<mrow><mrow><mrow><mi>x</mi><mo>∈</mo><mi>A</mi></mrow><mo>⇒</mo><mi>f</mi></mrow>\
+         <mo>≔</mo><mrow><mi>x</mi><mo>→</mo><mi>y</mi></mrow></mrow>"
+    );
+}
+
+#[test]
+fn test_to_mathml_text() {
+    let parser = crate::parser::Parser::new(r#"x "for all" n"#);
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        expression_to_mathml(&expression),
+        "<mrow><mrow><mi>x</mi><mo>\u{2062}</mo><mtext>for all</mtext></mrow>\
+         <mo>\u{2062}</mo><mi>n</mi></mrow>"
+    );
+}
+
+#[test]
+fn test_to_mathml_matrix() {
+    let parser = crate::parser::Parser::new("[[a, b], [c, d]]");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        expression_to_mathml(&expression),
+        "<mrow><mo>(</mo><mtable>\
+         <mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr>\
+         <mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd></mtr>\
+         </mtable><mo>)</mo></mrow>"
+    );
+}
+
+#[test]
+fn test_to_mathml_layout_functions() {
+    let parser = crate::parser::Parser::new("root(3, x) + abs(frac(1, 2))");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        expression_to_mathml(&expression),
+        "<mrow><mroot><mi>x</mi><mn>3</mn></mroot><mo>+</mo>\
+         <mrow><mo>|</mo><mfrac><mn>1</mn><mn>2</mn></mfrac><mo>|</mo></mrow></mrow>"
+    );
+}
+
+#[test]
+fn test_to_mathml_big_operators() {
+    let parser = crate::parser::Parser::new("sum_(i=1)^n x_i + integral_0^1 t dt");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        expression_to_mathml(&expression),
+        "<mrow><mrow><munderover><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi>\
+         </munderover><msub><mi>x</mi><mi>i</mi></msub></mrow><mo>+</mo>\
+         <mrow><msubsup><mo>∫</mo><mn>0</mn><mn>1</mn></msubsup>\
+         <mrow><mi>t</mi><mo>\u{2062}</mo><mi>dt</mi></mrow></mrow></mrow>"
+    );
+}
+
+#[test]
+fn test_to_mathml_decorations() {
+    let parser = crate::parser::Parser::new("vec(F) = m ddot(x) + underbrace(a + b)");
+    let math_equation = parser.complete_math_equation().unwrap();
+    assert_eq!(
+        to_mathml(&math_equation),
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"><mrow>\
+         <mover accent=\"true\"><mi>F</mi><mo>\u{2192}</mo></mover><mo>=</mo>\
+         <mrow><mrow><mi>m</mi><mo>\u{2062}</mo>\
+         <mover accent=\"true\"><mi>x</mi><mo>\u{a8}</mo></mover></mrow><mo>+</mo>\
+         <munder accentunder=\"true\"><mrow><mi>a</mi><mo>+</mo><mi>b</mi></mrow><mo>\u{23df}</mo></munder>\
+         </mrow></mrow></math>"
+    );
+}
+
+#[test]
+fn test_aligned_block_to_mathml() {
+    let parser = crate::parser::Parser::new("y &= 2x \\ &= 4");
+    let aligned_block = parser.aligned_block().unwrap();
+    assert_eq!(
+        aligned_block_to_mathml(&aligned_block),
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\">\
+         <mtable columnalign=\"right left\">\
+         <mtr><mtd><mrow><mi>y</mi></mrow></mtd><mtd><mrow><mo>=</mo>\
+         <mrow><mn>2</mn><mo>\u{2062}</mo><mi>x</mi></mrow></mrow></mtd></mtr>\
+         <mtr><mtd><mrow></mrow></mtd><mtd><mrow><mo>=</mo><mn>4</mn></mrow></mtd></mtr>\
+         </mtable></math>"
+    );
+}
@@ -1,39 +1,79 @@
-use core::str;
-use std::{cell::RefCell, fmt::Debug, str::from_utf8};
+use std::cell::RefCell;
 
+pub use crate::ast::Associativity;
 use crate::ast::{
-    Array, BinaryExpression, BinaryOperator, Expression, FunctionCall, MathEquation, Node,
-    PrimaryExpression, Span, UnaryExpression, UnaryOperator,
+    AlignedBlock, AlignedRow, Array, Attachment, BigOperator, BigOperatorKind, BinaryExpression,
+    BinaryOperator, Case, Cases, Decoration, DecorationKind, Expression, FunctionCall,
+    MathEquation, Matrix, Node, Number, PrimaryExpression, Radix, Span, UnaryExpression,
+    UnaryOperator,
 };
+use crate::error::{entries, line_col};
+pub use crate::error::{ParserError, TokenKind};
+use crate::layout::LayoutFunction;
 
 pub struct Parser<'a> {
-    pub input: &'a [u8],
+    pub input: &'a str,
     pub pos: RefCell<usize>,
+    pub operators: OperatorTable,
+    // In recovering mode errors are collected here instead of being returned,
+    // and the parser resynchronizes at the next `,`, `)`, `]` or `=`.
+    recovering: RefCell<bool>,
+    errors: RefCell<Vec<ParserError>>,
+    // Set while parsing several equations, where a line break outside of
+    // brackets ends the current equation.
+    newline_separates: RefCell<bool>,
+    // Cleared while parsing the value of a case, so that `x "if" x > 0` isn't
+    // read as a product of `x` and the text.
+    juxtapose_text: RefCell<bool>,
 }
 
-#[derive(Debug)]
-pub struct ParserError {
-    pub pos: usize,
-    pub message: String,
-}
-
+// math_equations := math_equation ~ ((";" | newline) ~ math_equation)*
 // math_equation := expression ("=" ~ expression)?
-// expression := unary_expression ~ (binary_op ~ unary_expression)*
+// aligned_block := aligned_row ~ ("\\" ~ aligned_row)* ~ "\\"?
+// aligned_row := expression? ~ ("&"? ~ "=" ~ expression | "&" ~ relation_op ~ expression)*  (at most one "&", e.g. `a &= b`, `&= c` or `&<= d`)
+// relation_op := a binary_op other than "+", "-", "−", "*", "/", "^", "&&" and "||"
+// expression := unary_expression ~ (binary_op? ~ unary_expression)*  (a missing binary_op is an implicit "*")
 // unary_expression := primary_expression | prefix_expression | postfix_expression
-// primary_expression :=  number | identifier | array | function_call | "(" ~ expression ~ ")"
-// array := "[" ~ expression ~ ("," ~ expression)* ~ "]"
+// primary_expression := atom ~ ("_" ~ atom ~ ("^" ~ superscript)? | "^" ~ superscript ~ "_" ~ atom)?
+// superscript := atom ~ ("^" ~ unary_expression)*  (right associative, `x_i^2^3` is `x_i^(2^3)`)
+// atom := number | identifier | text | array | matrix | cases | big_operator | decoration | function_call | "(" ~ expression ~ ")"
+// text := "\"" ~ ([^"\\] | "\\" ~ ["\\])* ~ "\""
+// array := "[" ~ expression ~ ("," ~ expression)* ~ "]"  (an array of arrays is a matrix)
+// matrix := "mat(" ~ row ~ (";" ~ row)* ~ ")"
+// row := expression ~ ("," ~ expression)*
+// cases := "cases(" ~ case ~ ("," ~ case)* ~ ")"
+// case := expression ~ (text ~ expression?)?  (e.g. `-x "if" x < 0` or `0 "otherwise"`)
+// big_operator := ("sum" | "prod" | "integral" | "lim" | "∑" | "∏" | "∫") ~ ("_" ~ limit)? ~ ("^" ~ atom)? ~ term
+// limit := "(" ~ (identifier ~ "=")? ~ expression ~ ")" | atom  (e.g. `_(i=0)`, `_(x -> 0)` or `_a`)
+// term := unary_expression ~ (binary_op? ~ unary_expression)*  (only "*", "/", "^" and implicit "*")
+// decoration := ("hat" | "bar" | "tilde" | "vec" | "dot" | "ddot" | "overline" | "underbrace") ~ "(" ~ expression ~ ")"
+//               (with any other number of arguments, e.g. `vec(a, b)`, it is a function_call)
 // function_call := identifier ~ ("(" ~ (expression ~ ("," ~ c_expression)*)? ~ ")")+
-// prefix_expression := unary_op ~ primary_expression
-// postfix_expression := primary_expression ~ unary_op
+// prefix_expression := unary_op ~ unary_expression ~ ("^" ~ unary_expression)*  (`-a^2` is `-(a^2)`)
+// postfix_expression := primary_expression ~ postfix_op
 // binary_op := "+" | "-" | "*" | "/" | "^" | "==" | "!=" | "&lt;" | "&gt;" | "&lt;=" | "&gt;=" | "&amp;&amp;" | "||"
-// unary_op := "-" | "!" | "++" | "--"
-// identifier := [a-zA-Z_][a-zA-Z0-9_]*
+//            | "->" | "=>" | "<=>" | "~" | "~=" | ":=" | "<<" | ">>" | "in" | "subset"
+//            | "×" | "·" | "⋅" | "÷" | "−" | "≠" | "≤" | "≥" | "∧" | "∨"
+//            | "→" | "⇒" | "⇔" | "∼" | "≅" | "≔" | "≪" | "≫" | "∈" | "⊂"
+// unary_op := "-" | "−" | "!" | "¬" | "++" | "--"
+// postfix_op := "!" | "++" | "--"
+// number := "0x" ~ [0-9a-fA-F]+ | [0-9]+ ~ ("." ~ [0-9]+)? ~ ([eE] ~ [+-]? ~ [0-9]+)?
+// identifier := alphabetic ~ alphanumeric*  (Unicode letters and digits)
 
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Self {
+        Parser::with_operators(input, OperatorTable::default())
+    }
+
+    pub fn with_operators(input: &'a str, operators: OperatorTable) -> Self {
         Parser {
-            input: input.as_bytes(),
+            input,
             pos: RefCell::new(0),
+            operators,
+            recovering: RefCell::new(false),
+            errors: RefCell::new(Vec::new()),
+            newline_separates: RefCell::new(false),
+            juxtapose_text: RefCell::new(true),
         }
     }
 
@@ -43,11 +83,7 @@ impl<'a> Parser<'a> {
             *self.pos.borrow_mut() += tag.len();
             Ok(Node::new(Span::span(start, start + tag.len()), ()))
         } else {
-            Err(self.parse_err(format!(
-                "Expected '{}' but found {:?}",
-                tag,
-                self.slice(start, start + tag.len())?
-            )))
+            Err(self.error(vec![TokenKind::Literal(tag.to_string())]))
         }
     }
 
@@ -55,22 +91,18 @@ impl<'a> Parser<'a> {
         let start = *self.pos.borrow();
         if self.starts_with(tag) {
             *self.pos.borrow_mut() += tag.len();
-            let ret = from_utf8(&self.input[start..start + tag.len()]).expect("Invalid utf8");
-            Ok(ret)
+            Ok(&self.input[start..start + tag.len()])
         } else {
-            Err(self.parse_err(format!(
-                "Expected '{}' but found {:?}",
-                tag,
-                self.slice(start, start + tag.len())?
-            )))
+            Err(self.error(vec![TokenKind::Literal(tag.to_string())]))
         }
     }
 
     pub fn slice(&self, start: usize, end: usize) -> Result<&str, ParserError> {
-        if start < self.input.len() && end <= self.input.len() {
-            Ok(from_utf8(&self.input[start..end]).expect("Invalid utf8"))
-        } else {
-            Err(self.parse_err(format!("Going out of bounds start: {} end: {}", start, end)))
+        match self.input.get(start..end) {
+            Some(slice) if start < self.input.len() => Ok(slice),
+            _ => Err(self
+                .error(Vec::new())
+                .with_hint(format!("{}..{} is out of bounds", start, end))),
         }
     }
 
@@ -87,12 +119,15 @@ impl<'a> Parser<'a> {
     }
 
     pub fn starts_with(&self, s: &str) -> bool {
-        *self.pos.borrow() < self.input.len()
-            && self.input[*self.pos.borrow()..].starts_with(s.as_bytes())
+        self.input
+            .get(*self.pos.borrow()..)
+            .is_some_and(|rest| !rest.is_empty() && rest.starts_with(s))
     }
 
+    // Advances past the current character, however many bytes it takes up.
     pub fn consume(&self) {
-        *self.pos.borrow_mut() += 1;
+        let len = self.cur_char().map_or(1, char::len_utf8);
+        *self.pos.borrow_mut() += len;
     }
 
     pub fn pos(&self) -> usize {
@@ -103,11 +138,22 @@ impl<'a> Parser<'a> {
         *self.pos.borrow_mut() = pos;
     }
 
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.input
+            .as_bytes()
+            .get(*self.pos.borrow() + offset)
+            .copied()
+    }
+
+    fn cur_char(&self) -> Option<char> {
+        self.input.get(*self.pos.borrow()..)?.chars().next()
+    }
+
     fn cur(&self) -> Result<u8, ParserError> {
         if *self.pos.borrow() < self.input.len() {
-            Ok(self.input[*self.pos.borrow()])
+            Ok(self.input.as_bytes()[*self.pos.borrow()])
         } else {
-            Err(self.parse_err("Unexpected end of input".to_string()))
+            Err(self.error(Vec::new()))
         }
     }
 
@@ -115,8 +161,7 @@ impl<'a> Parser<'a> {
         let start = self.pos();
         let mut expression_nodes = Vec::new();
         expression_nodes.push(self.expression()?);
-        self.multispace0()?;
-        while self.starts_with("=") {
+        while !self.end_of_line() && self.at_equals() {
             self.consume();
             expression_nodes.push(self.expression()?);
         }
@@ -128,25 +173,225 @@ impl<'a> Parser<'a> {
         ))
     }
 
+    // Parses a single equation that must make up the whole input. Unlike
+    // `math_equation`, anything left over is reported instead of ignored.
+    pub fn complete_math_equation(&self) -> Result<Node<MathEquation>, ParserError> {
+        let math_equation = self.math_equation()?;
+        self.multispace0()?;
+        if self.pos() < self.input.len() {
+            return Err(self.unconsumed(vec![
+                TokenKind::BinaryOperator,
+                TokenKind::Literal("=".to_string()),
+                TokenKind::EndOfInput,
+            ]));
+        }
+        Ok(math_equation)
+    }
+
+    // Parses lines separated by `\`, each an `=` chain with an optional `&`
+    // marking where the lines are aligned. The block must make up the whole
+    // input.
+    pub fn aligned_block(&self) -> Result<Node<AlignedBlock>, ParserError> {
+        self.multispace0()?;
+        let start = self.pos();
+        let mut rows = vec![self.aligned_row()?];
+        while self.starts_with("\\") {
+            self.consume();
+            self.multispace0()?;
+            // A line break after the last line is allowed, as in LaTeX.
+            if self.pos() >= self.input.len() {
+                break;
+            }
+            rows.push(self.aligned_row()?);
+        }
+        if self.pos() < self.input.len() {
+            return Err(self.unconsumed(vec![
+                TokenKind::BinaryOperator,
+                TokenKind::Literal("=".to_string()),
+                TokenKind::Literal("\\".to_string()),
+                TokenKind::EndOfInput,
+            ]));
+        }
+        Ok(Node::new(
+            Span::span(start, rows.last().unwrap().span.end),
+            AlignedBlock { rows },
+        ))
+    }
+
+    fn aligned_row(&self) -> Result<Node<AlignedRow>, ParserError> {
+        let start = self.pos();
+        let mut lhs = None;
+        if !self.starts_with("&") && !self.at_equals() {
+            lhs = Some(self.expression()?);
+        }
+        let mut rhs: Vec<Node<Expression>> = Vec::new();
+        let mut align = None;
+        let mut relation = None;
+        loop {
+            self.multispace0()?;
+            if self.starts_with("&") && !self.starts_with("&&") {
+                if align.is_some() {
+                    return Err(self
+                        .error(vec![TokenKind::Literal("=".to_string())])
+                        .with_hint("a line can only be aligned once".to_string()));
+                }
+                self.consume();
+                self.multispace0()?;
+                align = Some(rhs.len());
+                if !self.at_equals() {
+                    relation = Some(self.aligned_relation()?);
+                    rhs.push(self.expression()?);
+                    continue;
+                }
+            }
+            if !self.at_equals() {
+                break;
+            }
+            self.consume();
+            rhs.push(self.expression()?);
+        }
+        let end = rhs
+            .last()
+            .or(lhs.as_ref())
+            .map_or(start, |last| last.span.end);
+        Ok(Node::new(
+            Span::span(start, end),
+            AlignedRow {
+                lhs,
+                rhs,
+                align,
+                relation,
+            },
+        ))
+    }
+
+    // The relation after the `&` of an aligned row, such as the `<=` of
+    // `a &<= b`. Arithmetic and logical operators can't follow the `&`.
+    fn aligned_relation(&self) -> Result<Node<BinaryOperator>, ParserError> {
+        let start = self.pos();
+        match self.binary_operator() {
+            Ok(op) if is_relation(&op.node) => Ok(op),
+            _ => {
+                self.set_pos(start);
+                Err(self
+                    .error(vec![TokenKind::Literal("=".to_string())])
+                    .with_hint("'&' can only stand before '=' or a relation".to_string()))
+            }
+        }
+    }
+
+    // Parses equations separated by `;` or by line breaks. A line break inside
+    // brackets, or right after an operator, continues the current equation.
+    pub fn math_equations(&self) -> Result<Vec<Node<MathEquation>>, ParserError> {
+        let newline_separates = self.newline_separates.replace(true);
+        let math_equations = self.separated_math_equations();
+        *self.newline_separates.borrow_mut() = newline_separates;
+        math_equations
+    }
+
+    fn separated_math_equations(&self) -> Result<Vec<Node<MathEquation>>, ParserError> {
+        let mut math_equations = Vec::new();
+        loop {
+            while self.is_multispace().unwrap_or(false) || self.starts_with(";") {
+                self.consume();
+            }
+            if self.pos() >= self.input.len() {
+                return Ok(math_equations);
+            }
+            math_equations.push(self.math_equation()?);
+            if !self.end_of_line() && !self.starts_with(";") && self.pos() < self.input.len() {
+                return Err(self.unconsumed(vec![
+                    TokenKind::BinaryOperator,
+                    TokenKind::Literal("=".to_string()),
+                    TokenKind::Literal(";".to_string()),
+                    TokenKind::EndOfInput,
+                ]));
+            }
+        }
+    }
+
+    // Parses the whole input without stopping at the first error. Whatever
+    // could not be parsed is replaced by `PrimaryExpression::Error` nodes, so
+    // the returned tree is usable even while the input is incomplete.
+    pub fn math_equation_recovering(&self) -> (Node<MathEquation>, Vec<ParserError>) {
+        *self.recovering.borrow_mut() = true;
+        let start = self.pos();
+        let mut math_equation = self
+            .math_equation()
+            .expect("a recovering parser records errors instead of returning them");
+        self.multispace0().ok();
+        while self.pos() < self.input.len() {
+            let error = self.error(vec![
+                TokenKind::BinaryOperator,
+                TokenKind::Literal("=".to_string()),
+                TokenKind::EndOfInput,
+            ]);
+            self.errors.borrow_mut().push(error);
+            while self.pos() < self.input.len() && !self.at_equals() {
+                self.consume();
+            }
+            if self.starts_with("=") {
+                self.consume();
+                let expression = self
+                    .expression()
+                    .expect("a recovering parser records errors instead of returning them");
+                math_equation.node.expressions.push(expression);
+                self.multispace0().ok();
+            }
+        }
+        math_equation.span = Span::span(start, self.pos());
+        *self.recovering.borrow_mut() = false;
+        (math_equation, self.errors.take())
+    }
+
     pub fn expression(&self) -> Result<Node<Expression>, ParserError> {
+        self.expression_above(0)
+    }
+
+    // A product of powers, which is as far as the body of a big operator
+    // extends.
+    pub fn term(&self) -> Result<Node<Expression>, ParserError> {
+        self.expression_above(self.operators.precedence(&BinaryOperator::Mul))
+    }
+
+    // An expression whose binary operators have at least the given precedence;
+    // parsing stops before the first operator that binds more loosely.
+    fn expression_above(&self, min_precedence: u8) -> Result<Node<Expression>, ParserError> {
         let unary_expr = self.unary_expression()?;
+        let mut postfix = unary_expr.ends_with_postfix();
         let mut matched = Vec::new();
-        self.multispace0()?;
-        while let Ok(op) = self.binary_operator() {
+        while !self.end_of_line() {
+            let before = self.pos();
+            let (op, implicit) = match self.binary_operator() {
+                Ok(op) => (op, false),
+                // Operands written next to each other, as in `2x` or `(a)(b)`,
+                // are multiplied, but not after a postfix operator: `a! b`
+                // is not `(a!) b`.
+                Err(_) if !postfix && self.starts_with_juxtaposed() => (
+                    Node::new(Span::span(self.pos(), self.pos()), BinaryOperator::Mul),
+                    true,
+                ),
+                Err(_) => break,
+            };
+            if self.operators.precedence(&op.node) < min_precedence {
+                self.set_pos(before);
+                break;
+            }
             self.multispace0()?;
             let unary_expr = self.unary_expression()?;
-            matched.push((op, unary_expr));
-            self.multispace0()?;
+            postfix = unary_expr.ends_with_postfix();
+            matched.push((op, implicit, unary_expr));
         }
         let mut operand_stack: Vec<Node<Expression>> = vec![unary_expr];
-        let mut operator_stack: Vec<Node<BinaryOperator>> = Vec::new();
-        for (op, unary_expr) in matched {
-            while !operator_stack.is_empty()
-                && precedence(&operator_stack.last().unwrap().node) >= precedence(&op.node)
+        let mut operator_stack: Vec<(Node<BinaryOperator>, bool)> = Vec::new();
+        for (op, implicit, unary_expr) in matched {
+            while operator_stack
+                .last()
+                .is_some_and(|(top, _)| self.operators.reduces_before(&top.node, &op.node))
             {
                 let right = operand_stack.pop().unwrap();
                 let left = operand_stack.pop().unwrap();
-                let operator = operator_stack.pop().unwrap();
+                let (operator, implicit) = operator_stack.pop().unwrap();
                 let expr_span = Span {
                     start: left.span.start,
                     end: right.span.end,
@@ -159,17 +404,18 @@ impl<'a> Parser<'a> {
                             lhs: Box::new(left),
                             op: operator,
                             rhs: Box::new(right),
+                            implicit,
                         },
                     )),
                 ));
             }
             operand_stack.push(unary_expr);
-            operator_stack.push(op);
+            operator_stack.push((op, implicit));
         }
         while !operator_stack.is_empty() {
             let right = operand_stack.pop().unwrap();
             let left = operand_stack.pop().unwrap();
-            let operator = operator_stack.pop().unwrap();
+            let (operator, implicit) = operator_stack.pop().unwrap();
             let expr_span = Span {
                 start: left.span.start,
                 end: right.span.end,
@@ -182,6 +428,7 @@ impl<'a> Parser<'a> {
                         lhs: Box::new(left),
                         op: operator,
                         rhs: Box::new(right),
+                        implicit,
                     },
                 )),
             ));
@@ -190,23 +437,26 @@ impl<'a> Parser<'a> {
     }
 
     pub fn unary_expression(&self) -> Result<Node<Expression>, ParserError> {
+        self.multispace0()?;
         let start = self.pos();
         if let Ok(op) = self.unary_operator() {
-            let primary_expr = self.primary_expression()?;
+            // A power binds tighter than a prefix operator, so `-a^2` is the
+            // negation of `a^2`.
+            let operand = self.expression_above(self.operators.precedence(&BinaryOperator::Pow))?;
             return Ok(Node::new(
-                Span::span(start, self.pos()),
+                Span::span(start, operand.span.end),
                 Expression::UnaryExpression(Node::new(
-                    Span::span(start, self.pos()),
+                    Span::span(start, operand.span.end),
                     UnaryExpression {
                         op: Some(op),
-                        expr: Box::new(primary_expr),
+                        expr: Box::new(operand),
                         is_prefix: true,
                     },
                 )),
             ));
         }
         let primary_expr = self.primary_expression()?;
-        if let Ok(op) = self.unary_operator() {
+        if let Ok(op) = self.postfix_operator() {
             return Ok(Node::new(
                 Span::span(start, self.pos()),
                 Expression::UnaryExpression(Node::new(
@@ -223,13 +473,106 @@ impl<'a> Parser<'a> {
     }
 
     pub fn primary_expression(&self) -> Result<Node<Expression>, ParserError> {
+        self.multispace0()?;
+        let start = self.pos();
+        let base = self.atom()?;
+        // A superscript on its own is parsed as a power; it only becomes part
+        // of the attachment together with a subscript, in either order, as in
+        // `x_i^2` or `x^2_i`. Each script can be given only once.
+        let mut bottom = None;
+        let mut top = None;
+        loop {
+            if self.starts_with("^") && top.is_some() {
+                let error = self
+                    .error(vec![TokenKind::BinaryOperator])
+                    .with_hint("a variable can only have one superscript".to_string());
+                if !*self.recovering.borrow() {
+                    return Err(error);
+                }
+                self.errors.borrow_mut().push(error);
+                self.consume();
+                self.superscript()?;
+                continue;
+            }
+            if self.starts_with("_") {
+                if bottom.is_some() {
+                    let error = self
+                        .error(vec![TokenKind::BinaryOperator])
+                        .with_hint("a variable can only have one subscript".to_string());
+                    if !*self.recovering.borrow() {
+                        return Err(error);
+                    }
+                    // The repeated subscript is skipped when recovering.
+                    self.errors.borrow_mut().push(error);
+                    self.consume();
+                    self.atom()?;
+                    continue;
+                }
+                self.consume();
+                bottom = Some(Box::new(self.atom()?));
+            } else if self.starts_with("^") {
+                let before = self.pos();
+                let errors = self.errors.borrow().len();
+                self.consume();
+                match self.superscript() {
+                    Ok(superscript) if bottom.is_some() || self.starts_with("_") => {
+                        top = Some(Box::new(superscript));
+                    }
+                    _ => {
+                        self.set_pos(before);
+                        self.errors.borrow_mut().truncate(errors);
+                        break;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+        if bottom.is_none() {
+            return Ok(base);
+        }
+        Ok(Node::new(
+            Span::span(start, self.pos()),
+            Expression::PrimaryExpression(Node::new(
+                Span::span(start, self.pos()),
+                PrimaryExpression::Attachment(Node::new(
+                    Span::span(start, self.pos()),
+                    Attachment {
+                        base: Box::new(base),
+                        bottom,
+                        top,
+                    },
+                )),
+            )),
+        ))
+    }
+
+    // The superscript of an attachment. Like a power it associates to the
+    // right, so `x_i^2^3` is `x_i^(2^3)`.
+    fn superscript(&self) -> Result<Node<Expression>, ParserError> {
+        let start = self.pos();
+        let base = self.atom()?;
+        if !self.starts_with("^") {
+            return Ok(base);
+        }
+        self.consume();
+        let exponent = self.expression_above(self.operators.precedence(&BinaryOperator::Pow))?;
+        Ok(Expression::binary(
+            Span::span(start, self.pos()),
+            base,
+            BinaryOperator::Pow,
+            exponent,
+        ))
+    }
+
+    pub fn atom(&self) -> Result<Node<Expression>, ParserError> {
         self.multispace0()?;
         let start = self.pos();
         if self.starts_with("(") {
             self.consume();
-            let expr = self.expression()?;
+            let expr = self.nested(|| self.expression())?;
             self.multispace0()?;
-            self.tag(")")?;
+            self.closing(start, "(", ")", vec![TokenKind::BinaryOperator])?;
             return Ok(Node::new(
                 Span::span(start, self.pos()),
                 Expression::PrimaryExpression(Node::new(
@@ -238,23 +581,63 @@ impl<'a> Parser<'a> {
                 )),
             ));
         }
+        if self.starts_with("\"") {
+            let text = self.text()?;
+            return Ok(Node::new(
+                Span::span(start, self.pos()),
+                Expression::PrimaryExpression(Node::new(
+                    Span::span(start, self.pos()),
+                    PrimaryExpression::Text(text),
+                )),
+            ));
+        }
         if self.starts_with("[") {
-            let array = self.array()?;
+            let array = self.nested(|| self.array())?;
             return Ok(Node::new(
                 Span::span(start, self.pos()),
                 Expression::PrimaryExpression(Node::new(
                     Span::span(start, self.pos()),
-                    PrimaryExpression::Array(array),
+                    self.array_or_matrix(array)?,
+                )),
+            ));
+        }
+        if self.starts_with("cases(") {
+            let cases = self.nested(|| self.cases())?;
+            return Ok(Node::new(
+                Span::span(start, self.pos()),
+                Expression::PrimaryExpression(Node::new(
+                    Span::span(start, self.pos()),
+                    PrimaryExpression::Cases(cases),
+                )),
+            ));
+        }
+        if self.starts_with("mat(") {
+            let matrix = self.nested(|| self.matrix())?;
+            return Ok(Node::new(
+                Span::span(start, self.pos()),
+                Expression::PrimaryExpression(Node::new(
+                    Span::span(start, self.pos()),
+                    PrimaryExpression::Matrix(matrix),
+                )),
+            ));
+        }
+        if let Some(op) = self.big_operator_kind() {
+            let big_operator = self.big_operator(op)?;
+            return Ok(Node::new(
+                Span::span(start, self.pos()),
+                Expression::PrimaryExpression(Node::new(
+                    Span::span(start, self.pos()),
+                    PrimaryExpression::BigOperator(big_operator),
                 )),
             ));
         }
         if self.starts_with_func_call() {
-            let func_call = self.function_call()?;
+            let func_call = self.nested(|| self.function_call())?;
             return Ok(Node::new(
                 Span::span(start, self.pos()),
                 Expression::PrimaryExpression(Node::new(
                     Span::span(start, self.pos()),
-                    PrimaryExpression::FunctionCall(func_call),
+                    decoration_or_call(func_call),
                 )),
             ));
         }
@@ -276,10 +659,28 @@ impl<'a> Parser<'a> {
                 )),
             ));
         }
-        Err(self.parse_err(format!(
-            "Expected primary expression but found char {:?}",
-            self.slice(start, start + 1)?
-        )))
+        let error = self.error(vec![
+            TokenKind::Number,
+            TokenKind::Identifier,
+            TokenKind::Literal("(".to_string()),
+            TokenKind::Literal("[".to_string()),
+        ]);
+        if !*self.recovering.borrow() {
+            return Err(error);
+        }
+        self.errors.borrow_mut().push(error);
+        self.synchronize();
+        let text = self.slice(start, self.pos()).unwrap_or_default().trim_end();
+        Ok(Node::new(
+            Span::span(start, self.pos()),
+            Expression::PrimaryExpression(Node::new(
+                Span::span(start, self.pos()),
+                PrimaryExpression::Error(Node::new(
+                    Span::span(start, self.pos()),
+                    text.to_string(),
+                )),
+            )),
+        ))
     }
 
     pub fn array(&self) -> Result<Node<Array>, ParserError> {
@@ -291,31 +692,343 @@ impl<'a> Parser<'a> {
             self.consume();
             elements.push(self.expression()?);
         }
-        self.tag("]")?;
+        self.closing(
+            start,
+            "[",
+            "]",
+            vec![
+                TokenKind::BinaryOperator,
+                TokenKind::Literal(",".to_string()),
+            ],
+        )?;
         Ok(Node::new(Span::span(start, self.pos()), Array { elements }))
     }
 
+    pub fn matrix(&self) -> Result<Node<Matrix>, ParserError> {
+        let start = self.pos();
+        self.tag("mat")?;
+        let open = self.pos();
+        self.tag("(")?;
+        let mut rows = Vec::new();
+        loop {
+            let mut elements = vec![self.expression()?];
+            while self.starts_with(",") {
+                self.consume();
+                elements.push(self.expression()?);
+            }
+            let span = Span::span(
+                elements[0].span.start,
+                elements[elements.len() - 1].span.end,
+            );
+            rows.push(Node::new(span, Array { elements }));
+            if !self.starts_with(";") {
+                break;
+            }
+            self.consume();
+        }
+        self.closing(
+            open,
+            "(",
+            ")",
+            vec![
+                TokenKind::BinaryOperator,
+                TokenKind::Literal(",".to_string()),
+                TokenKind::Literal(";".to_string()),
+            ],
+        )?;
+        self.check_rows(&rows)?;
+        Ok(Node::new(Span::span(start, self.pos()), Matrix { rows }))
+    }
+
+    pub fn cases(&self) -> Result<Node<Cases>, ParserError> {
+        let start = self.pos();
+        self.tag("cases")?;
+        let open = self.pos();
+        self.tag("(")?;
+        let mut cases = vec![self.case()?];
+        while self.starts_with(",") {
+            self.consume();
+            cases.push(self.case()?);
+        }
+        self.closing(
+            open,
+            "(",
+            ")",
+            vec![
+                TokenKind::BinaryOperator,
+                TokenKind::Literal(",".to_string()),
+            ],
+        )?;
+        Ok(Node::new(Span::span(start, self.pos()), Cases { cases }))
+    }
+
+    fn case(&self) -> Result<Node<Case>, ParserError> {
+        let juxtapose_text = self.juxtapose_text.replace(false);
+        let value = self.expression();
+        *self.juxtapose_text.borrow_mut() = juxtapose_text;
+        let value = value?;
+        let mut end = value.span.end;
+        let mut text = None;
+        let mut condition = None;
+        self.multispace0()?;
+        if self.starts_with("\"") {
+            let words = self.text()?;
+            end = words.span.end;
+            text = Some(words);
+            self.multispace0()?;
+            if !self.starts_with(",") && !self.starts_with(")") {
+                let expression = self.expression()?;
+                end = expression.span.end;
+                condition = Some(expression);
+            }
+        }
+        Ok(Node::new(
+            Span::span(value.span.start, end),
+            Case {
+                value,
+                text,
+                condition,
+            },
+        ))
+    }
+
+    // An array whose elements are all arrays is read as a matrix with one row
+    // per inner array.
+    fn array_or_matrix(&self, array: Node<Array>) -> Result<PrimaryExpression, ParserError> {
+        let rows = array
+            .node
+            .elements
+            .iter()
+            .map(|element| match &element.node {
+                Expression::PrimaryExpression(primary) => match &primary.node {
+                    PrimaryExpression::Array(row) => Some(row.clone()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        let Some(rows) = rows else {
+            return Ok(PrimaryExpression::Array(array));
+        };
+        self.check_rows(&rows)?;
+        Ok(PrimaryExpression::Matrix(Node::new(
+            array.span,
+            Matrix { rows },
+        )))
+    }
+
+    // Reports the first row whose length differs from that of the first row.
+    fn check_rows(&self, rows: &[Node<Array>]) -> Result<(), ParserError> {
+        let columns = rows[0].node.elements.len();
+        let Some((i, row)) = rows
+            .iter()
+            .enumerate()
+            .find(|(_, row)| row.node.elements.len() != columns)
+        else {
+            return Ok(());
+        };
+        let error = ParserError {
+            span: row.span,
+            expected: vec![TokenKind::Row(columns)],
+            found: Some(self.input[row.span.start..row.span.end].to_string()),
+            hint: Some(format!(
+                "row {} has {} but row 1 has {}",
+                i + 1,
+                entries(row.node.elements.len()),
+                columns
+            )),
+        };
+        if !*self.recovering.borrow() {
+            return Err(error);
+        }
+        self.errors.borrow_mut().push(error);
+        Ok(())
+    }
+
+    // Reads the word or glyph of a big operator, but only where limits or a
+    // body follow it, so that `sum` on its own is still an identifier.
+    fn big_operator_kind(&self) -> Option<Node<BigOperatorKind>> {
+        let start = self.pos();
+        let op = if self.word("sum").is_some() || self.tag("∑").is_ok() {
+            BigOperatorKind::Sum
+        } else if self.word("prod").is_some() || self.tag("∏").is_ok() {
+            BigOperatorKind::Prod
+        } else if self.word("integral").is_some() || self.tag("∫").is_ok() {
+            BigOperatorKind::Integral
+        } else if self.word("lim").is_some() {
+            BigOperatorKind::Lim
+        } else {
+            return None;
+        };
+        let end = self.pos();
+        let glyph = !self.input[start..end].starts_with(char::is_alphabetic);
+        let limits = self.starts_with("_") || self.starts_with("^");
+        let body = !self.end_of_line()
+            && (glyph || self.pos() > end)
+            && (self.starts_with_juxtaposed() || self.cur().is_ok_and(|c| c.is_ascii_digit()));
+        if limits || body {
+            self.set_pos(end);
+            Some(Node::new(Span::span(start, end), op))
+        } else {
+            self.set_pos(start);
+            None
+        }
+    }
+
+    pub fn big_operator(
+        &self,
+        op: Node<BigOperatorKind>,
+    ) -> Result<Node<BigOperator>, ParserError> {
+        let mut index = None;
+        let mut lower = None;
+        let mut upper = None;
+        if self.starts_with("_") {
+            self.consume();
+            let (name, limit) = self.limit()?;
+            index = name;
+            lower = Some(Box::new(limit));
+        }
+        if self.starts_with("^") {
+            self.consume();
+            upper = Some(Box::new(self.atom()?));
+        }
+        let body = self.term()?;
+        Ok(Node::new(
+            Span::span(op.span.start, body.span.end),
+            BigOperator {
+                op,
+                index,
+                lower,
+                upper,
+                body: Box::new(body),
+            },
+        ))
+    }
+
+    // The lower limit of a big operator, with the index it binds if written
+    // as `(i = 0)`.
+    fn limit(&self) -> Result<(Option<Node<String>>, Node<Expression>), ParserError> {
+        if !self.starts_with("(") {
+            return Ok((None, self.atom()?));
+        }
+        let open = self.pos();
+        self.consume();
+        self.nested(|| {
+            self.multispace0()?;
+            let start = self.pos();
+            let mut index = None;
+            if let Ok(identifier) = self.identifier() {
+                self.multispace0()?;
+                if self.at_equals() {
+                    self.consume();
+                    index = Some(identifier);
+                } else {
+                    self.set_pos(start);
+                }
+            }
+            let lower = self.expression()?;
+            self.multispace0()?;
+            self.closing(open, "(", ")", vec![TokenKind::BinaryOperator])?;
+            Ok((index, lower))
+        })
+    }
+
     pub fn function_call(&self) -> Result<Node<FunctionCall>, ParserError> {
         let start = self.pos();
         let name = self.identifier()?;
+        let open = self.pos();
         self.tag("(")?;
         self.multispace0()?;
         let mut arguments = Vec::new();
-        if self.cur()? != b')' {
+        if !self.starts_with(")") {
             arguments.push(self.expression()?);
             while self.starts_with(",") {
                 self.consume();
                 arguments.push(self.expression()?);
             }
         }
-        self.tag(")")?;
-        Ok(Node::new(
+        self.closing(
+            open,
+            "(",
+            ")",
+            vec![
+                TokenKind::BinaryOperator,
+                TokenKind::Literal(",".to_string()),
+            ],
+        )?;
+        let function_call = Node::new(
             Span::span(start, self.pos()),
             FunctionCall { name, arguments },
-        ))
+        );
+        self.check_arity(&function_call)?;
+        Ok(function_call)
+    }
+
+    // Layout functions such as `frac` take a fixed number of arguments.
+    fn check_arity(&self, function_call: &Node<FunctionCall>) -> Result<(), ParserError> {
+        let Some(layout) = LayoutFunction::from_name(&function_call.node.name.node) else {
+            return Ok(());
+        };
+        if layout.arity() == function_call.node.arguments.len() {
+            return Ok(());
+        }
+        let span = function_call.span;
+        let error = ParserError {
+            span,
+            expected: vec![TokenKind::Arguments(layout.arity())],
+            found: Some(self.input[span.start..span.end].to_string()),
+            hint: Some(format!(
+                "usage: {}({})",
+                function_call.node.name.node,
+                layout.parameters().join(", ")
+            )),
+        };
+        if !*self.recovering.borrow() {
+            return Err(error);
+        }
+        self.errors.borrow_mut().push(error);
+        Ok(())
+    }
+
+    // A double-quoted string. Within it, `\"` stands for a quote and `\\` for a
+    // backslash; any other backslash is kept as written.
+    pub fn text(&self) -> Result<Node<String>, ParserError> {
+        let start = self.pos();
+        self.tag("\"")?;
+        let mut text = String::new();
+        while let Some(c) = self.cur_char() {
+            if c == '"' {
+                break;
+            }
+            self.consume();
+            if c == '\\' && (self.starts_with("\"") || self.starts_with("\\")) {
+                text.push(self.cur_char().unwrap());
+                self.consume();
+            } else {
+                text.push(c);
+            }
+        }
+        self.closing(start, "\"", "\"", Vec::new())?;
+        Ok(Node::new(Span::span(start, self.pos()), text))
     }
 
     pub fn binary_operator(&self) -> Result<Node<BinaryOperator>, ParserError> {
+        // Operators that start with another operator have to be tried first.
+        let longest_first = [
+            ("<=>", BinaryOperator::Iff),
+            ("->", BinaryOperator::Arrow),
+            ("=>", BinaryOperator::Implies),
+            ("~=", BinaryOperator::Cong),
+            ("~", BinaryOperator::Sim),
+            (":=", BinaryOperator::Define),
+            ("<<", BinaryOperator::MuchLess),
+            (">>", BinaryOperator::MuchGreater),
+        ];
+        for (tag, operator) in longest_first {
+            if let Ok(op) = self.tag_node(tag) {
+                return Ok(Node::new(op.span, operator));
+            }
+        }
         if let Ok(op) = self.tag_node("+") {
             Ok(Node::new(op.span, BinaryOperator::Add))
         } else if let Ok(op) = self.tag_node("-") {
@@ -342,68 +1055,178 @@ impl<'a> Parser<'a> {
             Ok(Node::new(op.span, BinaryOperator::And))
         } else if let Ok(op) = self.tag_node("||") {
             Ok(Node::new(op.span, BinaryOperator::Or))
+        } else if let Some(op) = self.unicode_binary_operator() {
+            Ok(op)
+        } else if let Some(op) = self.word("in") {
+            Ok(Node::new(op.span, BinaryOperator::In))
+        } else if let Some(op) = self.word("subset") {
+            Ok(Node::new(op.span, BinaryOperator::Subset))
         } else {
-            Err(self.parse_err(format!(
-                "Expected binary operator but found {}",
-                self.cur()? as char
-            )))
+            Err(self.error(vec![TokenKind::BinaryOperator]))
         }
     }
 
+    // The typographic forms of the binary operators, e.g. `≤` for `<=`.
+    fn unicode_binary_operator(&self) -> Option<Node<BinaryOperator>> {
+        let operator = match self.cur_char()? {
+            '×' | '·' | '⋅' => BinaryOperator::Mul,
+            '÷' => BinaryOperator::Div,
+            '−' => BinaryOperator::Sub,
+            '≠' => BinaryOperator::Ne,
+            '≤' => BinaryOperator::Le,
+            '≥' => BinaryOperator::Ge,
+            '∧' => BinaryOperator::And,
+            '∨' => BinaryOperator::Or,
+            '→' => BinaryOperator::Arrow,
+            '⇒' => BinaryOperator::Implies,
+            '⇔' => BinaryOperator::Iff,
+            '∼' => BinaryOperator::Sim,
+            '≅' => BinaryOperator::Cong,
+            '≔' => BinaryOperator::Define,
+            '≪' => BinaryOperator::MuchLess,
+            '≫' => BinaryOperator::MuchGreater,
+            '∈' => BinaryOperator::In,
+            '⊂' => BinaryOperator::Subset,
+            _ => return None,
+        };
+        let start = self.pos();
+        self.consume();
+        Some(Node::new(Span::span(start, self.pos()), operator))
+    }
+
+    // A keyword operator such as `in`, which has to stand as a word of its own
+    // so that identifiers like `index` aren't split.
+    fn word(&self, word: &str) -> Option<Node<()>> {
+        let start = self.pos();
+        let end = start + word.len();
+        if !self.starts_with(word) || self.input[end..].starts_with(char::is_alphanumeric) {
+            return None;
+        }
+        self.set_pos(end);
+        Some(Node::new(Span::span(start, end), ()))
+    }
+
     pub fn unary_operator(&self) -> Result<Node<UnaryOperator>, ParserError> {
         let start = self.pos();
-        let operator = if let Ok(_) = self.tag("!") {
+        let operator = if self.tag("!").is_ok() || self.tag("¬").is_ok() {
             UnaryOperator::Not
-        } else if let Ok(_) = self.tag("-") {
+        } else if self.tag("-").is_ok() || self.tag("−").is_ok() {
             UnaryOperator::Neg
-        } else if let Ok(_) = self.tag("++") {
+        } else if self.tag("++").is_ok() {
+            UnaryOperator::Inc
+        } else if self.tag("--").is_ok() {
+            UnaryOperator::Dec
+        } else {
+            return Err(self.error(vec![TokenKind::UnaryOperator]));
+        };
+        Ok(Node::new(Span::span(start, self.pos()), operator))
+    }
+
+    // Only the operators that can't be mistaken for the start of a binary
+    // operator may follow their operand, so `a-b` stays a subtraction.
+    pub fn postfix_operator(&self) -> Result<Node<UnaryOperator>, ParserError> {
+        let start = self.pos();
+        let operator = if self.tag("++").is_ok() {
             UnaryOperator::Inc
-        } else if let Ok(_) = self.tag("--") {
+        } else if self.tag("--").is_ok() {
             UnaryOperator::Dec
+        } else if !self.starts_with("!=") && self.tag("!").is_ok() {
+            UnaryOperator::Not
         } else {
-            return Err(self.parse_err(format!(
-                "Expected unary operator but found {}",
-                self.cur()? as char
-            )));
+            return Err(self.error(vec![TokenKind::UnaryOperator]));
         };
+        // An increment or decrement followed by an operand is a binary and a
+        // prefix operator instead, so `a--b` is `a - (-b)`.
+        if operator != UnaryOperator::Not && self.starts_with_operand() {
+            self.set_pos(start);
+            return Err(self.error(vec![TokenKind::UnaryOperator]));
+        }
         Ok(Node::new(Span::span(start, self.pos()), operator))
     }
 
-    pub fn number(&self) -> Result<Node<i64>, ParserError> {
+    // Whether an operand follows on the same line, possibly after spaces.
+    fn starts_with_operand(&self) -> bool {
+        let rest = self.input[self.pos()..].trim_start_matches([' ', '\t']);
+        rest.chars()
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '(' | '[' | '"' | '∑' | '∏' | '∫'))
+    }
+
+    pub fn number(&self) -> Result<Node<Number>, ParserError> {
         let start = self.pos();
-        let mut is_negative = false;
-        if self.starts_with("-") {
+        if (self.starts_with("0x") || self.starts_with("0X"))
+            && self.peek(2).is_some_and(|c| c.is_ascii_hexdigit())
+        {
+            self.set_pos(start + 2);
+            let integer = self.digits(|c| c.is_ascii_hexdigit());
+            return Ok(Node::new(
+                Span::span(start, self.pos()),
+                Number {
+                    lexeme: self.slice(start, self.pos())?.to_string(),
+                    radix: Radix::Hexadecimal,
+                    integer: integer.to_string(),
+                    fraction: None,
+                    exponent: None,
+                },
+            ));
+        }
+        let integer = self.digits(|c| c.is_ascii_digit());
+        if integer.is_empty() {
+            return Err(self.error(vec![TokenKind::Number]));
+        }
+        let mut fraction = None;
+        if self.starts_with(".") && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
             self.consume();
-            is_negative = true;
+            fraction = Some(self.digits(|c| c.is_ascii_digit()).to_string());
         }
-        while let Ok(c) = self.cur() {
-            if c.is_ascii_digit() {
+        // An exponent marker that isn't followed by digits is left for whatever
+        // comes next, so `2e` still reads as a number followed by `e`.
+        let mut exponent = None;
+        if self.starts_with("e") || self.starts_with("E") {
+            let sign = match self.peek(1) {
+                Some(b'+') | Some(b'-') => 1,
+                _ => 0,
+            };
+            if self.peek(1 + sign).is_some_and(|c| c.is_ascii_digit()) {
                 self.consume();
-            } else {
-                break;
+                let exponent_start = self.pos();
+                self.set_pos(exponent_start + sign);
+                self.digits(|c| c.is_ascii_digit());
+                exponent = Some(self.slice(exponent_start, self.pos())?.to_string());
             }
         }
-        let num_str = self.slice(start, self.pos())?;
-        let num = num_str
-            .parse::<i64>()
-            .map_err(|_| self.parse_err("Invalid number".to_string()))?;
         Ok(Node::new(
             Span::span(start, self.pos()),
-            if is_negative { -num } else { num },
+            Number {
+                lexeme: self.slice(start, self.pos())?.to_string(),
+                radix: Radix::Decimal,
+                integer: integer.to_string(),
+                fraction,
+                exponent,
+            },
         ))
     }
 
+    fn digits(&self, is_digit: impl Fn(u8) -> bool) -> &str {
+        let start = self.pos();
+        while let Ok(c) = self.cur() {
+            if is_digit(c) {
+                self.consume();
+            } else {
+                break;
+            }
+        }
+        &self.input[start..self.pos()]
+    }
+
     pub fn identifier(&self) -> Result<Node<String>, ParserError> {
         let start = self.pos();
-        if !self.cur()?.is_ascii_alphabetic() {
-            return Err(self.parse_err(format!(
-                "Expected identifier but found {:?}",
-                self.cur()? as char
-            )));
+        if !self.cur_char().is_some_and(char::is_alphabetic) {
+            return Err(self.error(vec![TokenKind::Identifier]));
         }
         self.consume();
-        while let Ok(c) = self.cur() {
-            if c.is_ascii_alphanumeric() || c == b'_' {
+        while let Some(c) = self.cur_char() {
+            if c.is_alphanumeric() {
                 self.consume();
             } else {
                 break;
@@ -415,11 +1238,126 @@ impl<'a> Parser<'a> {
         ))
     }
 
-    fn parse_err(&self, message: String) -> ParserError {
+    // Error at the current position, pointing at the character found there.
+    fn error(&self, expected: Vec<TokenKind>) -> ParserError {
+        let start = self.pos();
+        let found = self.input.get(start..).and_then(|rest| rest.chars().next());
         ParserError {
-            pos: self.pos(),
-            message,
+            span: Span::span(start, start + found.map_or(0, char::len_utf8)),
+            expected,
+            found: found.map(String::from),
+            hint: None,
+        }
+    }
+
+    // Consumes the delimiter closing the one at `open`. Besides the delimiter,
+    // the error lists whatever else could have continued the construct.
+    fn closing(
+        &self,
+        open: usize,
+        opening: &str,
+        closing: &str,
+        mut alternatives: Vec<TokenKind>,
+    ) -> Result<(), ParserError> {
+        if self.tag(closing).is_ok() {
+            return Ok(());
+        }
+        alternatives.push(TokenKind::Literal(closing.to_string()));
+        let error = self.error(alternatives);
+        if error.found.as_deref() == Some("=") {
+            return Err(error.with_hint(
+                "'=' only separates the sides of an equation; use '==' to compare values"
+                    .to_string(),
+            ));
+        }
+        let (line, column) = line_col(self.input, open);
+        let error = error.with_hint(format!(
+            "the '{}' at {}:{} is not closed",
+            opening, line, column
+        ));
+        if !*self.recovering.borrow() {
+            return Err(error);
         }
+        // Skip whatever is left before the delimiter, if it turns up at all.
+        self.errors.borrow_mut().push(error);
+        self.synchronize();
+        self.tag(closing).ok();
+        Ok(())
+    }
+
+    // Error for input left over after a complete parse, spanning all of it.
+    fn unconsumed(&self, expected: Vec<TokenKind>) -> ParserError {
+        let mut error = self.error(expected);
+        let rest = &self.input[self.pos()..];
+        error.span.end = self.pos() + rest.trim_end().len();
+        match error.found.as_deref() {
+            Some(")") => error.with_hint("there is no '(' for this ')' to close".to_string()),
+            Some("]") => error.with_hint("there is no '[' for this ']' to close".to_string()),
+            _ => error,
+        }
+    }
+
+    // Skips whitespace and reports whether it ended the equation. That only
+    // happens at a line break while parsing several equations, in which case
+    // the line break is left for the caller.
+    fn end_of_line(&self) -> bool {
+        let start = self.pos();
+        self.multispace0().ok();
+        if *self.newline_separates.borrow() && self.input[start..self.pos()].contains('\n') {
+            self.set_pos(start);
+            return true;
+        }
+        false
+    }
+
+    // Runs `parse` inside brackets, where line breaks never end the equation
+    // and text can always be juxtaposed.
+    fn nested<T>(&self, parse: impl FnOnce() -> T) -> T {
+        let newline_separates = self.newline_separates.replace(false);
+        let juxtapose_text = self.juxtapose_text.replace(true);
+        let result = parse();
+        *self.newline_separates.borrow_mut() = newline_separates;
+        *self.juxtapose_text.borrow_mut() = juxtapose_text;
+        result
+    }
+
+    // Skips ahead to the next `,`, `)`, `]` or `=` that is not nested inside
+    // brackets opened along the way, leaving it unconsumed.
+    fn synchronize(&self) {
+        let mut depth = 0;
+        while let Ok(c) = self.cur() {
+            match c {
+                b'(' | b'[' => depth += 1,
+                b')' | b']' if depth > 0 => depth -= 1,
+                b',' | b')' | b']' if depth == 0 => return,
+                _ if depth == 0 && self.at_equals() => return,
+                _ => {}
+            }
+            if ["==", "!=", "<=", ">="]
+                .iter()
+                .any(|op| self.starts_with(op))
+            {
+                self.consume();
+            }
+            self.consume();
+        }
+    }
+
+    // Whether the input continues with the `=` separating two sides of the
+    // equation, as opposed to a comparison operator.
+    fn at_equals(&self) -> bool {
+        self.starts_with("=") && !self.starts_with("==") && !self.starts_with("=>")
+    }
+
+    // Whether an operand starts here that can be multiplied by juxtaposition.
+    // Digits are excluded so that `2 3` isn't read as a product, while the
+    // big-operator glyphs are included so that `2 ∫ x` is.
+    fn starts_with_juxtaposed(&self) -> bool {
+        self.cur_char().is_some_and(|c| {
+            c.is_alphabetic()
+                || matches!(c, '(' | '[' | '∑' | '∏' | '∫')
+                || (c == '"' && *self.juxtapose_text.borrow())
+        })
     }
 
     fn starts_with_func_call(&self) -> bool {
@@ -430,14 +1368,88 @@ impl<'a> Parser<'a> {
     }
 }
 
-fn precedence(binary_operator: &BinaryOperator) -> u8 {
-    match binary_operator {
-        BinaryOperator::Or => 1,                      // ||
-        BinaryOperator::And => 2,                     // &&
-        BinaryOperator::Eq | BinaryOperator::Ne => 3, // ==, !=
-        BinaryOperator::Lt | BinaryOperator::Gt | BinaryOperator::Le | BinaryOperator::Ge => 4, // <, >, <=, >=
-        BinaryOperator::Add | BinaryOperator::Sub => 5, // +, -
-        BinaryOperator::Mul | BinaryOperator::Div | BinaryOperator::Pow => 6, // *, /, ^
+// A call of a decoration name with a single argument, such as `hat(x)`, is a
+// decoration. With any other number of arguments, as in the Typst column
+// vector `vec(a, b)`, it stays a function call.
+fn decoration_or_call(function_call: Node<FunctionCall>) -> PrimaryExpression {
+    let kind = match function_call.node.name.node.as_str() {
+        "hat" => DecorationKind::Hat,
+        "bar" => DecorationKind::Bar,
+        "tilde" => DecorationKind::Tilde,
+        "vec" => DecorationKind::Vec,
+        "dot" => DecorationKind::Dot,
+        "ddot" => DecorationKind::Ddot,
+        "overline" => DecorationKind::Overline,
+        "underbrace" => DecorationKind::Underbrace,
+        _ => return PrimaryExpression::FunctionCall(function_call),
+    };
+    if function_call.node.arguments.len() != 1 {
+        return PrimaryExpression::FunctionCall(function_call);
+    }
+    let FunctionCall {
+        name,
+        mut arguments,
+    } = function_call.node;
+    PrimaryExpression::Decoration(Node::new(
+        function_call.span,
+        Decoration {
+            kind: Node::new(name.span, kind),
+            base: Box::new(arguments.remove(0)),
+        },
+    ))
+}
+
+// Operators that relate the two sides of an aligned row, as opposed to
+// arithmetic and logical ones.
+fn is_relation(op: &BinaryOperator) -> bool {
+    !matches!(
+        op,
+        BinaryOperator::Add
+            | BinaryOperator::Sub
+            | BinaryOperator::Mul
+            | BinaryOperator::Div
+            | BinaryOperator::Pow
+            | BinaryOperator::And
+            | BinaryOperator::Or
+    )
+}
+
+// Precedence and associativity of the binary operators. Operators with a
+// higher precedence bind tighter. Entries set on the table override the
+// defaults of `BinaryOperator::precedence` and `BinaryOperator::associativity`.
+#[derive(Debug, Clone, Default)]
+pub struct OperatorTable {
+    overrides: Vec<(BinaryOperator, u8, Associativity)>,
+}
+
+impl OperatorTable {
+    pub fn set(&mut self, op: BinaryOperator, precedence: u8, associativity: Associativity) {
+        self.overrides
+            .retain(|(overridden, _, _)| *overridden != op);
+        self.overrides.push((op, precedence, associativity));
+    }
+
+    pub fn precedence(&self, op: &BinaryOperator) -> u8 {
+        self.lookup(op).map_or_else(|| op.precedence(), |(p, _)| p)
+    }
+
+    pub fn associativity(&self, op: &BinaryOperator) -> Associativity {
+        self.lookup(op)
+            .map_or_else(|| op.associativity(), |(_, a)| a)
+    }
+
+    // Whether `a top b next c` groups as `(a top b) next c`.
+    pub fn reduces_before(&self, top: &BinaryOperator, next: &BinaryOperator) -> bool {
+        let (top, next_precedence) = (self.precedence(top), self.precedence(next));
+        top > next_precedence
+            || (top == next_precedence && self.associativity(next) == Associativity::Left)
+    }
+
+    fn lookup(&self, op: &BinaryOperator) -> Option<(u8, Associativity)> {
+        self.overrides
+            .iter()
+            .find(|(overridden, _, _)| overridden == op)
+            .map(|(_, p, a)| (*p, *a))
     }
 }
 
@@ -447,3 +1459,436 @@ fn test_math_equation() {
     let math_equation = parser.math_equation().unwrap();
     println!("{:#?}", math_equation);
 }
+
+#[test]
+fn test_number_literals() {
+    let cases = [
+        ("42", Radix::Decimal, "42", None, None, 42.0),
+        ("2.75", Radix::Decimal, "2", Some("75"), None, 2.75),
+        ("1e-9", Radix::Decimal, "1", None, Some("-9"), 1e-9),
+        (
+            "6.02e23",
+            Radix::Decimal,
+            "6",
+            Some("02"),
+            Some("23"),
+            6.02e23,
+        ),
+        ("0xFF", Radix::Hexadecimal, "FF", None, None, 255.0),
+    ];
+    for (input, radix, integer, fraction, exponent, value) in cases {
+        let number = Parser::new(input).number().unwrap().node;
+        assert_eq!(number.lexeme, input);
+        assert_eq!(number.radix, radix);
+        assert_eq!(number.integer, integer);
+        assert_eq!(number.fraction.as_deref(), fraction);
+        assert_eq!(number.exponent.as_deref(), exponent);
+        assert_eq!(number.to_f64(), value);
+    }
+}
+
+#[test]
+fn test_number_stops_before_non_numeric_suffix() {
+    let parser = Parser::new("2e");
+    assert_eq!(parser.number().unwrap().node.lexeme, "2");
+    assert_eq!(parser.pos(), 1);
+}
+
+#[test]
+fn test_parser_error_expected_tokens() {
+    let source = "f(a, b";
+    let err = Parser::new(source).math_equation().unwrap_err();
+    assert_eq!(
+        err.expected,
+        vec![
+            TokenKind::BinaryOperator,
+            TokenKind::Literal(",".to_string()),
+            TokenKind::Literal(")".to_string()),
+        ]
+    );
+    assert_eq!(err.found, None);
+    assert_eq!(
+        err.render(source),
+        "error: expected binary operator, ',' or ')' but found end of input\n  \
+         --> 1:7\n  \
+         |\n\
+         1 | f(a, b\n  \
+         |       ^\n  \
+         = hint: the '(' at 1:2 is not closed\n"
+    );
+
+    let err = Parser::new("a + * b").math_equation().unwrap_err();
+    assert_eq!(
+        err.message(),
+        "expected number, identifier, '(' or '[' but found \"*\""
+    );
+    assert_eq!((err.span.start, err.span.end), (4, 5));
+}
+
+#[test]
+fn test_math_equation_recovering() {
+    let parser = Parser::new("f(a, ) + [1, * 2] = (b");
+    let (math_equation, errors) = parser.math_equation_recovering();
+    assert_eq!(
+        errors.iter().map(|e| e.span.start).collect::<Vec<_>>(),
+        vec![5, 13, 22]
+    );
+    assert_eq!(math_equation.node.expressions.len(), 2);
+    assert_eq!(
+        crate::typst::to_typst(&math_equation),
+        "f(a, #text(fill: red)[\"?\"]) + vec(1, #text(fill: red)[\"* 2\"]) = (b)"
+    );
+
+    let (_, errors) = Parser::new("(a # b) + c = d").math_equation_recovering();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].found.as_deref(), Some("#"));
+}
+
+#[test]
+fn test_complete_math_equation() {
+    let source = "a + b ) c";
+    let err = Parser::new(source).complete_math_equation().unwrap_err();
+    assert_eq!((err.span.start, err.span.end), (6, 9));
+    assert_eq!(
+        err.render(source),
+        "error: expected binary operator, '=' or end of input but found \")\"\n  \
+         --> 1:7\n  \
+         |\n\
+         1 | a + b ) c\n  \
+         |       ^^^\n  \
+         = hint: there is no '(' for this ')' to close\n"
+    );
+
+    let math_equation = Parser::new("a-b = c++ ").complete_math_equation().unwrap();
+    assert_eq!(crate::typst::to_typst(&math_equation), "a - b = c++");
+}
+
+#[test]
+fn test_math_equations() {
+    let parser = Parser::new("a = b; c = d\ne = f +\n  g\n\nh = f(x,\n  y)\n-x = y\n");
+    let math_equations = parser.math_equations().unwrap();
+    let typst = math_equations
+        .iter()
+        .map(crate::typst::to_typst)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        typst,
+        vec!["a = b", "c = d", "e = f + g", "h = f(x, y)", "-x = y"]
+    );
+
+    let err = Parser::new("a = b\nc )").math_equations().unwrap_err();
+    assert_eq!((err.span.start, err.span.end), (8, 9));
+}
+
+// Fully parenthesized rendering of the tree, for asserting its shape.
+#[cfg(test)]
+fn shape(expression: &Node<Expression>) -> String {
+    match &expression.node {
+        Expression::BinaryExpression(binary) => format!(
+            "({} {:?} {})",
+            shape(&binary.node.lhs),
+            binary.node.op.node,
+            shape(&binary.node.rhs)
+        ),
+        Expression::UnaryExpression(unary) => match &unary.node.op {
+            Some(op) if unary.node.is_prefix => {
+                format!("({:?} {})", op.node, shape(&unary.node.expr))
+            }
+            Some(op) => format!("({} {:?})", shape(&unary.node.expr), op.node),
+            None => shape(&unary.node.expr),
+        },
+        Expression::PrimaryExpression(primary) => match &primary.node {
+            PrimaryExpression::GroupedExpression(inner) => shape(inner),
+            _ => crate::typst::expression_to_typst(expression),
+        },
+    }
+}
+
+#[test]
+fn test_operator_precedence_and_associativity() {
+    let cases = [
+        ("2^3^2", "(2 Pow (3 Pow 2))"),
+        ("a*b^2", "(a Mul (b Pow 2))"),
+        ("a^2*b", "((a Pow 2) Mul b)"),
+        ("a/b^c^d", "(a Div (b Pow (c Pow d)))"),
+        ("a - b - c", "((a Sub b) Sub c)"),
+        ("a / b / c", "((a Div b) Div c)"),
+        ("a / b * c", "((a Div b) Mul c)"),
+        ("a + b * c - d", "((a Add (b Mul c)) Sub d)"),
+        ("(a - b)^2^x", "((a Sub b) Pow (2 Pow x))"),
+        ("-a^2", "(Neg (a Pow 2))"),
+        (
+            "a < b + 1 && b == c || d",
+            "(((a Lt (b Add 1)) And (b Eq c)) Or d)",
+        ),
+        ("a || b && c != d >= e", "(a Or (b And (c Ne (d Ge e))))"),
+    ];
+    for (input, expected) in cases {
+        let expression = Parser::new(input).expression().unwrap();
+        assert_eq!(shape(&expression), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_custom_operator_table() {
+    let mut operators = OperatorTable::default();
+    operators.set(BinaryOperator::Sub, 8, Associativity::Right);
+    operators.set(BinaryOperator::Pow, 10, Associativity::Left);
+    let parser = Parser::with_operators("a - b - c + 2^3^2", operators);
+    assert_eq!(
+        shape(&parser.expression().unwrap()),
+        "((a Sub (b Sub c)) Add ((2 Pow 3) Pow 2))"
+    );
+}
+
+#[test]
+fn test_implicit_multiplication() {
+    let cases = [
+        ("2x", "(2 Mul x)"),
+        ("2(a+b)", "(2 Mul (a Add b))"),
+        ("a b c", "((a Mul b) Mul c)"),
+        ("(x+1)(x-1)", "((x Add 1) Mul (x Sub 1))"),
+        ("2x^2 + 3x", "((2 Mul (x Pow 2)) Add (3 Mul x))"),
+        ("a_(i j)", "a_(i j)"),
+        ("a--b", "(a Sub (Neg b))"),
+        ("a-- b", "(a Sub (Neg b))"),
+        ("a-- + b", "((a Dec) Add b)"),
+    ];
+    for (input, expected) in cases {
+        let expression = Parser::new(input).complete_math_equation().unwrap();
+        assert_eq!(
+            shape(&expression.node.expressions[0]),
+            expected,
+            "{}",
+            input
+        );
+    }
+
+    let Expression::BinaryExpression(binary) = Parser::new("2 y").expression().unwrap().node else {
+        panic!("expected a product");
+    };
+    assert!(binary.node.implicit);
+    assert!(Parser::new("2 3").complete_math_equation().is_err());
+
+    let err = Parser::new("a! b").complete_math_equation().unwrap_err();
+    assert_eq!((err.span.start, err.span.end), (3, 4));
+}
+
+#[test]
+fn test_unicode_input() {
+    let cases = [
+        ("α + β", "(α Add β)"),
+        ("x ≤ y ∧ ¬z", "((x Le y) And (Not z))"),
+        ("a × b ÷ c · d", "(((a Mul b) Div c) Mul d)"),
+        ("x ≥ 0 ∨ x ≠ 1", "((x Ge 0) Or (x Ne 1))"),
+        ("2θ", "(2 Mul θ)"),
+        ("a−b × −c", "(a Sub (b Mul (Neg c)))"),
+        ("α ∑ β", "(α Mul sum β)"),
+        ("2 ∫ x", "(2 Mul integral x)"),
+    ];
+    for (input, expected) in cases {
+        let expression = Parser::new(input).complete_math_equation().unwrap();
+        assert_eq!(
+            shape(&expression.node.expressions[0]),
+            expected,
+            "{}",
+            input
+        );
+    }
+
+    let (math_equation, errors) = Parser::new("(a € b) = γ").math_equation_recovering();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(math_equation.node.expressions.len(), 2);
+}
+
+#[test]
+fn test_relation_operators() {
+    let cases = [
+        ("f := x -> x^2", "(f Define (x Arrow (x Pow 2)))"),
+        ("a => b => c <=> d", "((a Implies (b Implies c)) Iff d)"),
+        ("a && b => c || d", "((a And b) Implies (c Or d))"),
+        ("x ~ y ~= z", "((x Sim y) Cong z)"),
+        ("a << b + 1 >> c", "((a MuchLess (b Add 1)) MuchGreater c)"),
+        ("x in A && A subset B", "((x In A) And (A Subset B))"),
+        ("i index", "(i Mul \"index\")"),
+        (
+            "f ≔ x ∈ A ⇒ x ≪ 1",
+            "(f Define ((x In A) Implies (x MuchLess 1)))",
+        ),
+    ];
+    for (input, expected) in cases {
+        let expression = Parser::new(input).complete_math_equation().unwrap();
+        assert_eq!(
+            shape(&expression.node.expressions[0]),
+            expected,
+            "{}",
+            input
+        );
+    }
+
+    let math_equation = Parser::new("a => b = c").complete_math_equation().unwrap();
+    assert_eq!(math_equation.node.expressions.len(), 2);
+}
+
+#[test]
+fn test_text() {
+    let text = Parser::new(r#""a \"b\" \\ \c""#).text().unwrap();
+    assert_eq!(text.node, r#"a "b" \ \c"#);
+
+    let source = r#"x = "open"#;
+    let err = Parser::new(source).complete_math_equation().unwrap_err();
+    assert_eq!(err.hint.as_deref(), Some("the '\"' at 1:5 is not closed"));
+}
+
+#[test]
+fn test_matrix() {
+    for input in ["mat(1, 2; 3, 4)", "[[1, 2], [3, 4]]"] {
+        let expression = Parser::new(input).complete_math_equation().unwrap();
+        assert_eq!(
+            crate::typst::to_typst(&expression),
+            "mat(1, 2; 3, 4)",
+            "{}",
+            input
+        );
+    }
+
+    let source = "[[1, 2], [3]]";
+    let err = Parser::new(source).complete_math_equation().unwrap_err();
+    assert_eq!((err.span.start, err.span.end), (9, 12));
+    assert_eq!(err.message(), "expected row of 2 entries but found \"[3]\"");
+    assert_eq!(
+        err.hint.as_deref(),
+        Some("row 2 has 1 entry but row 1 has 2")
+    );
+
+    let err = Parser::new("mat(a, b; c, d, e)")
+        .complete_math_equation()
+        .unwrap_err();
+    assert_eq!((err.span.start, err.span.end), (10, 17));
+}
+
+#[test]
+fn test_layout_function_arity() {
+    let source = "y = 1 + frac(a)";
+    let err = Parser::new(source).complete_math_equation().unwrap_err();
+    assert_eq!((err.span.start, err.span.end), (8, 15));
+    assert_eq!(err.message(), "expected 2 arguments but found \"frac(a)\"");
+    assert_eq!(
+        err.hint.as_deref(),
+        Some("usage: frac(numerator, denominator)")
+    );
+
+    // The call is kept when recovering, and rendered as an ordinary call.
+    let (math_equation, errors) = Parser::new("sqrt(a, b)").math_equation_recovering();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        crate::latex::to_latex(&math_equation),
+        "\\operatorname{sqrt}\\left( a, b \\right)"
+    );
+}
+
+#[test]
+fn test_attachments() {
+    let cases = [
+        ("x_i^2", "x_(i)^(2)"),
+        ("x^2_i", "x_(i)^(2)"),
+        ("x^2 + y_(n+1)", "((x Pow 2) Add y_(n + 1))"),
+        ("x_i^2^3", "x_(i)^(2^(3))"),
+        ("x^2^3_i", "(x Pow 2_(i)^(3))"),
+        ("x^2^3", "(x Pow (2 Pow 3))"),
+    ];
+    for (input, expected) in cases {
+        let expression = Parser::new(input).complete_math_equation().unwrap();
+        assert_eq!(
+            shape(&expression.node.expressions[0]),
+            expected,
+            "{}",
+            input
+        );
+    }
+
+    let source = "a_b_c";
+    let err = Parser::new(source).complete_math_equation().unwrap_err();
+    assert_eq!((err.span.start, err.span.end), (3, 4));
+    assert_eq!(err.message(), "expected binary operator but found \"_\"");
+    assert_eq!(
+        err.hint.as_deref(),
+        Some("a variable can only have one subscript")
+    );
+
+    let err = Parser::new("x^2_i^3").complete_math_equation().unwrap_err();
+    assert_eq!((err.span.start, err.span.end), (5, 6));
+    assert_eq!(
+        err.hint.as_deref(),
+        Some("a variable can only have one superscript")
+    );
+
+    let (math_equation, errors) = Parser::new("x_1_2 = y").math_equation_recovering();
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].span.start, errors[0].span.end), (3, 4));
+    assert_eq!(crate::typst::to_typst(&math_equation), "x_(1) = y");
+}
+
+#[test]
+fn test_big_operators() {
+    let cases = [
+        ("sum_(i=0)^n i^2", "sum_(i = 0)^(n) i^(2)"),
+        ("sum_i a_i b_i + c", "sum_(i) a_(i) b_(i) + c"),
+        ("integral_a^b f(x) dx", "integral_(a)^(b) f(x) \"dx\""),
+        ("lim_(x -> 0) sin(x) / x", "lim_(x -> 0) frac(sin(x), x)"),
+        ("∏_(k=1)^n k = m", "product_(k = 1)^(n) k = m"),
+        ("sum = 3", "sum = 3"),
+    ];
+    for (input, expected) in cases {
+        let math_equation = Parser::new(input).complete_math_equation().unwrap();
+        assert_eq!(
+            crate::typst::to_typst(&math_equation),
+            expected,
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_aligned_block() {
+    let source = "(a + b)^2 &= (a + b)(a + b) \\\n  &= a^2 + 2 a b + b^2 \\";
+    let aligned_block = Parser::new(source).aligned_block().unwrap();
+    let rows = &aligned_block.node.rows;
+    assert_eq!(rows.len(), 2);
+    assert_eq!((rows[0].node.rhs.len(), rows[0].node.align), (1, Some(0)));
+    assert_eq!(rows[1].node.lhs, None);
+    assert_eq!(
+        crate::typst::aligned_block_to_typst(&aligned_block),
+        "(a + b)^(2) &= (a + b) (a + b) \\\n&= a^(2) + 2 a b + b^(2)"
+    );
+
+    let err = Parser::new("a &= b &= c").aligned_block().unwrap_err();
+    assert_eq!((err.span.start, err.span.end), (7, 8));
+    assert_eq!(err.message(), "expected '=' but found \"&\"");
+
+    let source = "x^2 + 1 &>= 2x \\ f &-> g \\ &== c";
+    let aligned_block = Parser::new(source).aligned_block().unwrap();
+    assert_eq!(
+        crate::typst::aligned_block_to_typst(&aligned_block),
+        "x^(2) + 1 &>= 2 x \\\nf &-> g \\\n&equiv c"
+    );
+
+    let err = Parser::new("a &+ b").aligned_block().unwrap_err();
+    assert_eq!((err.span.start, err.span.end), (3, 4));
+    assert_eq!(
+        err.hint.as_deref(),
+        Some("'&' can only stand before '=' or a relation")
+    );
+}
+
+#[test]
+fn test_decoration_or_call() {
+    let math_equation = Parser::new("vec(a, b) + dot(u, v) = dot(x)")
+        .complete_math_equation()
+        .unwrap();
+    assert_eq!(
+        crate::typst::to_typst(&math_equation),
+        "op(\"vec\")(a, b) + op(\"dot\")(u, v) = dot(x)"
+    );
+}
@@ -0,0 +1,846 @@
+use std::cmp::Ordering;
+
+use crate::ast::{
+    Array, Attachment, BigOperator, BinaryExpression, BinaryOperator, Case, Cases, Decoration,
+    Expression, FunctionCall, Matrix, Node, Number, PrimaryExpression, Radix, Span,
+    UnaryExpression, UnaryOperator,
+};
+use crate::bigint::BigInt;
+use crate::typst::expression_to_typst;
+
+// Term rewriting over expressions. A `Simplifier` holds an ordered list of
+// rules and rewrites bottom-up until no rule applies any more. A rule only
+// looks at the node it is given; the simplifier takes care of visiting every
+// node. Rewritten nodes keep the span of the node they replace, so the result
+// can still be related back to the source.
+
+const MAX_PASSES: usize = 64;
+
+pub trait Rule {
+    fn name(&self) -> &str;
+
+    // Returns the rewritten expression, or `None` when the rule does not apply.
+    // A rule must not return an expression equal to its input.
+    fn apply(&self, expression: &Node<Expression>) -> Option<Node<Expression>>;
+}
+
+pub struct Simplifier {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Simplifier {
+    pub fn new() -> Self {
+        Simplifier { rules: Vec::new() }
+    }
+
+    pub fn add_rule(&mut self, rule: Box<dyn Rule>) {
+        self.rules.push(rule);
+    }
+
+    pub fn simplify(&self, expression: &Node<Expression>) -> Node<Expression> {
+        let mut current = expression.clone();
+        for _ in 0..MAX_PASSES {
+            let next = self.rewrite(&current);
+            if next == current {
+                break;
+            }
+            current = next;
+        }
+        current.ungrouped().clone()
+    }
+
+    fn rewrite(&self, expression: &Node<Expression>) -> Node<Expression> {
+        let expression = map_children(expression, |child| self.rewrite(child));
+        for rule in &self.rules {
+            if let Some(rewritten) = rule.apply(&expression) {
+                return rewritten;
+            }
+        }
+        expression
+    }
+}
+
+impl Default for Simplifier {
+    fn default() -> Self {
+        let mut simplifier = Simplifier::new();
+        simplifier.add_rule(Box::new(FlattenGroups));
+        simplifier.add_rule(Box::new(ConstantFolding));
+        simplifier.add_rule(Box::new(IdentityElements));
+        simplifier.add_rule(Box::new(CollectLikeTerms));
+        simplifier.add_rule(Box::new(CanonicalOrder));
+        simplifier
+    }
+}
+
+pub fn simplify(expression: &Node<Expression>) -> Node<Expression> {
+    Simplifier::default().simplify(expression)
+}
+
+// `((x))` becomes `(x)`, parentheses around a single operand are dropped, and
+// parentheses that the operator precedence makes redundant are removed.
+pub struct FlattenGroups;
+
+impl Rule for FlattenGroups {
+    fn name(&self) -> &str {
+        "flatten-groups"
+    }
+
+    fn apply(&self, expression: &Node<Expression>) -> Option<Node<Expression>> {
+        match &expression.node {
+            Expression::PrimaryExpression(primary) => {
+                let PrimaryExpression::GroupedExpression(inner) = &primary.node else {
+                    return None;
+                };
+                let ungrouped = inner.ungrouped();
+                match &ungrouped.node {
+                    Expression::PrimaryExpression(_) => Some(ungrouped.clone()),
+                    // Whether a binary or unary expression needs its parentheses
+                    // is decided by the parent operator below.
+                    _ if !std::ptr::eq(ungrouped, &**inner) => {
+                        Some(Expression::grouped(expression.span, ungrouped.clone()))
+                    }
+                    _ => None,
+                }
+            }
+            Expression::BinaryExpression(binary) => {
+                let (lhs, rhs) = (binary.node.lhs.ungrouped(), binary.node.rhs.ungrouped());
+                let rebuilt = if binary.node.implicit {
+                    Expression::juxtaposed(expression.span, lhs.clone(), rhs.clone())
+                } else {
+                    Expression::binary(
+                        expression.span,
+                        lhs.clone(),
+                        binary.node.op.node.clone(),
+                        rhs.clone(),
+                    )
+                };
+                (rebuilt != *expression).then_some(rebuilt)
+            }
+            Expression::UnaryExpression(_) => None,
+        }
+    }
+}
+
+// Arithmetic on two numeric literals, done exactly in decimal so that
+// `0.1 + 0.2` is `0.3`. Divisions and powers are only folded when the result
+// is an integer, so `1/3` stays as written, and results too large for an f64
+// are not folded at all.
+pub struct ConstantFolding;
+
+impl Rule for ConstantFolding {
+    fn name(&self) -> &str {
+        "constant-folding"
+    }
+
+    fn apply(&self, expression: &Node<Expression>) -> Option<Node<Expression>> {
+        match &expression.node {
+            Expression::BinaryExpression(binary) => {
+                let a = Decimal::from_expression(&binary.node.lhs)?;
+                let b = Decimal::from_expression(&binary.node.rhs)?;
+                let value = match binary.node.op.node {
+                    BinaryOperator::Add => a.add(&b),
+                    BinaryOperator::Sub => a.add(&b.neg()),
+                    BinaryOperator::Mul => a.mul(&b),
+                    BinaryOperator::Div => a.div(&b)?,
+                    BinaryOperator::Pow => a.pow(&b)?,
+                    _ => return None,
+                };
+                let folded = value.to_expression(expression.span);
+                Decimal::from_expression(&folded).map(|_| folded)
+            }
+            // --x
+            Expression::UnaryExpression(_) => negated(negated(expression)?).cloned(),
+            Expression::PrimaryExpression(_) => None,
+        }
+    }
+}
+
+// x + 0, 0 + x, x - 0, 0 - x, x * 1, 1 * x, x * 0, x / 1, x^1 and x^0.
+pub struct IdentityElements;
+
+impl Rule for IdentityElements {
+    fn name(&self) -> &str {
+        "identity-elements"
+    }
+
+    fn apply(&self, expression: &Node<Expression>) -> Option<Node<Expression>> {
+        let Expression::BinaryExpression(binary) = &expression.node else {
+            return None;
+        };
+        let (lhs, rhs) = (&*binary.node.lhs, &*binary.node.rhs);
+        let span = expression.span;
+        match (&binary.node.op.node, lhs.as_number(), rhs.as_number()) {
+            (BinaryOperator::Add, Some(0.0), _) => Some(rhs.clone()),
+            (BinaryOperator::Add | BinaryOperator::Sub, _, Some(0.0)) => Some(lhs.clone()),
+            (BinaryOperator::Sub, Some(0.0), _) => {
+                Some(Expression::unary(span, UnaryOperator::Neg, rhs.clone()))
+            }
+            (BinaryOperator::Mul, Some(0.0), _) | (BinaryOperator::Mul, _, Some(0.0)) => {
+                Some(Expression::number(span, 0.0))
+            }
+            (BinaryOperator::Mul, Some(1.0), _) => Some(rhs.clone()),
+            (BinaryOperator::Mul | BinaryOperator::Div | BinaryOperator::Pow, _, Some(1.0)) => {
+                Some(lhs.clone())
+            }
+            (BinaryOperator::Pow, _, Some(0.0)) => Some(Expression::number(span, 1.0)),
+            _ => None,
+        }
+    }
+}
+
+// Sums whose terms differ only by a numeric coefficient are combined, so that
+// `2*x + 3 - x + 1` becomes `x + 4`.
+pub struct CollectLikeTerms;
+
+impl Rule for CollectLikeTerms {
+    fn name(&self) -> &str {
+        "collect-like-terms"
+    }
+
+    fn apply(&self, expression: &Node<Expression>) -> Option<Node<Expression>> {
+        if !is_sum(expression) {
+            return None;
+        }
+        let mut terms = Vec::new();
+        sum_terms(expression, 1.0, &mut terms);
+        // The coefficient is kept juxtaposed if the first such term was.
+        let mut collected: Vec<(Decimal, Option<Node<Expression>>, bool)> = Vec::new();
+        for (sign, term) in &terms {
+            let (coefficient, factor) = split_coefficient(term);
+            let coefficient = if *sign < 0.0 {
+                coefficient.neg()
+            } else {
+                coefficient
+            };
+            let key = factor.map(term_key);
+            match collected
+                .iter_mut()
+                .find(|(_, other, _)| other.as_ref().map(term_key) == key)
+            {
+                Some((sum, _, _)) => *sum = sum.add(&coefficient),
+                None => collected.push((coefficient, factor.cloned(), is_juxtaposed(term))),
+            }
+        }
+        if collected.len() == terms.len() {
+            return None;
+        }
+        let signed = collected
+            .into_iter()
+            .filter(|(coefficient, _, _)| !coefficient.mantissa.is_zero())
+            .map(|(coefficient, factor, juxtaposed)| {
+                let span = expression.span;
+                let sign = if coefficient.mantissa.is_negative() {
+                    -1.0
+                } else {
+                    1.0
+                };
+                let magnitude = coefficient.abs();
+                let term = match factor {
+                    None => magnitude.to_expression(span),
+                    Some(factor) if magnitude == Decimal::one() => factor,
+                    Some(factor) if juxtaposed => {
+                        Expression::juxtaposed(span, magnitude.to_expression(span), factor)
+                    }
+                    Some(factor) => Expression::binary(
+                        span,
+                        magnitude.to_expression(span),
+                        BinaryOperator::Mul,
+                        factor,
+                    ),
+                };
+                (sign, term)
+            })
+            .collect();
+        Some(build_sum(expression, signed))
+    }
+}
+
+// Operands of sums and products are sorted: in a product numeric coefficients
+// come first, in a sum constants come last, and otherwise terms are ordered by
+// their base and by descending power, e.g. `x^2 + 3*x + 1`.
+pub struct CanonicalOrder;
+
+impl Rule for CanonicalOrder {
+    fn name(&self) -> &str {
+        "canonical-order"
+    }
+
+    fn apply(&self, expression: &Node<Expression>) -> Option<Node<Expression>> {
+        if is_sum(expression) {
+            let mut terms = Vec::new();
+            sum_terms(expression, 1.0, &mut terms);
+            let mut sorted = terms.clone();
+            sorted.sort_by(|(_, a), (_, b)| compare_terms(a, b, true));
+            if sorted == terms {
+                return None;
+            }
+            return Some(build_sum(expression, sorted));
+        }
+        if is_operator(expression, &BinaryOperator::Mul) {
+            let mut factors = Vec::new();
+            product_factors(expression, &mut factors);
+            let mut sorted = factors.clone();
+            sorted.sort_by(|a, b| compare_terms(a, b, false));
+            if sorted == factors {
+                return None;
+            }
+            let juxtaposed = is_juxtaposed(expression);
+            let mut sorted = sorted.into_iter();
+            let first = sorted.next()?;
+            return Some(sorted.fold(first, |product, factor| {
+                if juxtaposed {
+                    Expression::juxtaposed(expression.span, product, factor)
+                } else {
+                    Expression::binary(expression.span, product, BinaryOperator::Mul, factor)
+                }
+            }));
+        }
+        None
+    }
+}
+
+fn compare_terms(a: &Node<Expression>, b: &Node<Expression>, constants_last: bool) -> Ordering {
+    match (a.as_number().is_some(), b.as_number().is_some()) {
+        (true, false) if constants_last => Ordering::Greater,
+        (false, true) if constants_last => Ordering::Less,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (true, true) => Ordering::Equal,
+        (false, false) => {
+            let (a_base, a_power) = base_and_power(a);
+            let (b_base, b_power) = base_and_power(b);
+            a_base
+                .cmp(&b_base)
+                .then(b_power.partial_cmp(&a_power).unwrap_or(Ordering::Equal))
+        }
+    }
+}
+
+fn base_and_power(term: &Node<Expression>) -> (String, f64) {
+    let (_, factor) = split_coefficient(term);
+    let Some(factor) = factor else {
+        return (String::new(), 0.0);
+    };
+    if let Expression::BinaryExpression(binary) = &factor.node {
+        if binary.node.op.node == BinaryOperator::Pow {
+            if let Some(power) = binary.node.rhs.as_number() {
+                return (expression_to_typst(binary.node.lhs.ungrouped()), power);
+            }
+        }
+    }
+    (expression_to_typst(factor), 1.0)
+}
+
+// Splits `c * t`, `t * c` and plain numbers into a numeric coefficient and the
+// remaining factor.
+fn split_coefficient(term: &Node<Expression>) -> (Decimal, Option<&Node<Expression>>) {
+    if let Some(value) = Decimal::from_expression(term) {
+        return (value, None);
+    }
+    let term = term.ungrouped();
+    if let Expression::BinaryExpression(binary) = &term.node {
+        if binary.node.op.node == BinaryOperator::Mul {
+            if let Some(value) = Decimal::from_expression(&binary.node.lhs) {
+                return (value, Some(binary.node.rhs.ungrouped()));
+            }
+            if let Some(value) = Decimal::from_expression(&binary.node.rhs) {
+                return (value, Some(binary.node.lhs.ungrouped()));
+            }
+        }
+    }
+    if let Some(inner) = negated(term) {
+        let (value, factor) = split_coefficient(inner);
+        return (value.neg(), factor);
+    }
+    (Decimal::one(), Some(term))
+}
+
+fn is_juxtaposed(expression: &Node<Expression>) -> bool {
+    matches!(&expression.ungrouped().node, Expression::BinaryExpression(binary) if binary.node.implicit)
+}
+
+// Terms are compared by their structure, so `2x` and `2*x` are like terms.
+fn term_key(term: &Node<Expression>) -> String {
+    format!("{:?}", explicit(term))
+}
+
+fn explicit(expression: &Node<Expression>) -> Node<Expression> {
+    let mut expression = map_children(expression, explicit);
+    if let Expression::BinaryExpression(binary) = &mut expression.node {
+        binary.node.implicit = false;
+    }
+    expression
+}
+
+fn is_sum(expression: &Node<Expression>) -> bool {
+    is_operator(expression, &BinaryOperator::Add) || is_operator(expression, &BinaryOperator::Sub)
+}
+
+fn is_operator(expression: &Node<Expression>, op: &BinaryOperator) -> bool {
+    matches!(&expression.ungrouped().node, Expression::BinaryExpression(binary) if binary.node.op.node == *op)
+}
+
+// Flattens a chain of additions and subtractions, looking through parentheses,
+// into signed terms.
+fn sum_terms(expression: &Node<Expression>, sign: f64, terms: &mut Vec<(f64, Node<Expression>)>) {
+    if let Expression::BinaryExpression(binary) = &expression.ungrouped().node {
+        match binary.node.op.node {
+            BinaryOperator::Add => {
+                sum_terms(&binary.node.lhs, sign, terms);
+                sum_terms(&binary.node.rhs, sign, terms);
+                return;
+            }
+            BinaryOperator::Sub => {
+                sum_terms(&binary.node.lhs, sign, terms);
+                sum_terms(&binary.node.rhs, -sign, terms);
+                return;
+            }
+            _ => {}
+        }
+    }
+    terms.push((sign, expression.ungrouped().clone()));
+}
+
+fn product_factors(expression: &Node<Expression>, factors: &mut Vec<Node<Expression>>) {
+    if let Expression::BinaryExpression(binary) = &expression.ungrouped().node {
+        if binary.node.op.node == BinaryOperator::Mul {
+            product_factors(&binary.node.lhs, factors);
+            product_factors(&binary.node.rhs, factors);
+            return;
+        }
+    }
+    factors.push(expression.ungrouped().clone());
+}
+
+fn build_sum(
+    expression: &Node<Expression>,
+    terms: Vec<(f64, Node<Expression>)>,
+) -> Node<Expression> {
+    let span = expression.span;
+    let mut terms = terms.into_iter();
+    let Some((sign, first)) = terms.next() else {
+        return Expression::number(span, 0.0);
+    };
+    let first = if sign < 0.0 {
+        Expression::unary(span, UnaryOperator::Neg, first)
+    } else {
+        first
+    };
+    terms.fold(first, |sum, (sign, term)| {
+        let op = if sign < 0.0 {
+            BinaryOperator::Sub
+        } else {
+            BinaryOperator::Add
+        };
+        Expression::binary(span, sum, op, term)
+    })
+}
+
+fn negated(expression: &Node<Expression>) -> Option<&Node<Expression>> {
+    match &expression.ungrouped().node {
+        Expression::UnaryExpression(unary)
+            if unary.node.is_prefix
+                && unary.node.op.as_ref().map(|op| &op.node) == Some(&UnaryOperator::Neg) =>
+        {
+            Some(&unary.node.expr)
+        }
+        _ => None,
+    }
+}
+
+// Largest number of digits after the decimal point a literal may have to be
+// folded, so that `1e-100000` isn't expanded.
+const MAX_SCALE: u32 = 1000;
+
+// The exact value `mantissa / 10^scale` of a numeric literal, with no trailing
+// zeros after the decimal point.
+#[derive(Debug, Clone, PartialEq)]
+struct Decimal {
+    mantissa: BigInt,
+    scale: u32,
+}
+
+impl Decimal {
+    fn one() -> Decimal {
+        Decimal::integer(BigInt::from(1))
+    }
+
+    fn integer(mantissa: BigInt) -> Decimal {
+        Decimal { mantissa, scale: 0 }
+    }
+
+    // The value of a numeric literal, possibly negated or parenthesized.
+    // Literals that don't fit in an f64, such as `1e400`, have none.
+    fn from_expression(expression: &Node<Expression>) -> Option<Decimal> {
+        if let Some(inner) = negated(expression) {
+            return Decimal::from_expression(inner).map(|value| value.neg());
+        }
+        let Expression::PrimaryExpression(primary) = &expression.ungrouped().node else {
+            return None;
+        };
+        let PrimaryExpression::Number(number) = &primary.node else {
+            return None;
+        };
+        let number = &number.node;
+        if let Some(integer) = number.to_bigint() {
+            return Some(Decimal::integer(integer));
+        }
+        if number.radix != Radix::Decimal || !number.to_f64().is_finite() {
+            return None;
+        }
+        let fraction = number.fraction.as_deref().unwrap_or("");
+        let exponent: i64 = number
+            .exponent
+            .as_deref()
+            .map_or(Some(0), |e| e.parse().ok())?;
+        let digits = BigInt::from_str_radix(&format!("{}{}", number.integer, fraction), 10)?;
+        let scale = fraction.len() as i64 - exponent;
+        if scale > i64::from(MAX_SCALE) {
+            return None;
+        }
+        let value = match u32::try_from(scale) {
+            Ok(scale) => Decimal {
+                mantissa: digits,
+                scale,
+            },
+            // The literal is finite, so its exponent is at most a few hundred.
+            Err(_) => Decimal::integer(&digits * &power_of_ten((-scale) as u32)),
+        };
+        Some(value.normalized())
+    }
+
+    fn neg(&self) -> Decimal {
+        Decimal {
+            mantissa: -self.mantissa.clone(),
+            scale: self.scale,
+        }
+    }
+
+    fn abs(&self) -> Decimal {
+        if self.mantissa.is_negative() {
+            self.neg()
+        } else {
+            self.clone()
+        }
+    }
+
+    fn add(&self, other: &Decimal) -> Decimal {
+        let (a, b, scale) = self.aligned(other);
+        Decimal {
+            mantissa: &a + &b,
+            scale,
+        }
+        .normalized()
+    }
+
+    fn mul(&self, other: &Decimal) -> Decimal {
+        Decimal {
+            mantissa: &self.mantissa * &other.mantissa,
+            scale: self.scale + other.scale,
+        }
+        .normalized()
+    }
+
+    // The quotient, if it is an integer.
+    fn div(&self, other: &Decimal) -> Option<Decimal> {
+        let (a, b, _) = self.aligned(other);
+        match a.div_rem(&b)? {
+            (quotient, remainder) if remainder.is_zero() => Some(Decimal::integer(quotient)),
+            _ => None,
+        }
+    }
+
+    // The power, if both operands are integers and the exponent is not
+    // negative. Any other power of a literal isn't an integer.
+    fn pow(&self, exponent: &Decimal) -> Option<Decimal> {
+        if self.scale != 0 || exponent.scale != 0 {
+            return None;
+        }
+        let exponent = exponent.mantissa.to_u32()?;
+        self.mantissa.checked_pow(exponent).map(Decimal::integer)
+    }
+
+    // Both mantissas over the larger of the two scales.
+    fn aligned(&self, other: &Decimal) -> (BigInt, BigInt, u32) {
+        let scale = self.scale.max(other.scale);
+        (
+            &self.mantissa * &power_of_ten(scale - self.scale),
+            &other.mantissa * &power_of_ten(scale - other.scale),
+            scale,
+        )
+    }
+
+    fn normalized(mut self) -> Decimal {
+        let ten = BigInt::from(10);
+        while self.scale > 0 {
+            match self.mantissa.div_rem(&ten) {
+                Some((quotient, remainder)) if remainder.is_zero() => {
+                    self.mantissa = quotient;
+                    self.scale -= 1;
+                }
+                _ => break,
+            }
+        }
+        self
+    }
+
+    fn to_expression(&self, span: Span) -> Node<Expression> {
+        let digits = self.abs().mantissa.to_string();
+        let digits = format!("{:0>width$}", digits, width = self.scale as usize + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - self.scale as usize);
+        let fraction = (!fraction.is_empty()).then(|| fraction.to_string());
+        let number = Expression::primary(
+            span,
+            PrimaryExpression::Number(Node::new(
+                span,
+                Number {
+                    lexeme: match &fraction {
+                        Some(fraction) => format!("{}.{}", integer, fraction),
+                        None => integer.to_string(),
+                    },
+                    radix: Radix::Decimal,
+                    integer: integer.to_string(),
+                    fraction,
+                    exponent: None,
+                },
+            )),
+        );
+        if self.mantissa.is_negative() {
+            Expression::unary(span, UnaryOperator::Neg, number)
+        } else {
+            number
+        }
+    }
+}
+
+fn power_of_ten(exponent: u32) -> BigInt {
+    BigInt::from(10).pow(exponent)
+}
+
+fn map_children(
+    expression: &Node<Expression>,
+    mut f: impl FnMut(&Node<Expression>) -> Node<Expression>,
+) -> Node<Expression> {
+    let span = expression.span;
+    let node = match &expression.node {
+        Expression::UnaryExpression(unary) => Expression::UnaryExpression(Node::new(
+            unary.span,
+            UnaryExpression {
+                op: unary.node.op.clone(),
+                expr: Box::new(f(&unary.node.expr)),
+                is_prefix: unary.node.is_prefix,
+            },
+        )),
+        Expression::BinaryExpression(binary) => Expression::BinaryExpression(Node::new(
+            binary.span,
+            BinaryExpression {
+                lhs: Box::new(f(&binary.node.lhs)),
+                op: binary.node.op.clone(),
+                rhs: Box::new(f(&binary.node.rhs)),
+                implicit: binary.node.implicit,
+            },
+        )),
+        Expression::PrimaryExpression(primary) => {
+            let mapped = match &primary.node {
+                PrimaryExpression::GroupedExpression(inner) => {
+                    PrimaryExpression::GroupedExpression(Box::new(f(inner)))
+                }
+                PrimaryExpression::FunctionCall(function_call) => {
+                    PrimaryExpression::FunctionCall(Node::new(
+                        function_call.span,
+                        FunctionCall {
+                            name: function_call.node.name.clone(),
+                            arguments: function_call.node.arguments.iter().map(&mut f).collect(),
+                        },
+                    ))
+                }
+                PrimaryExpression::Attachment(attachment) => {
+                    PrimaryExpression::Attachment(Node::new(
+                        attachment.span,
+                        Attachment {
+                            base: Box::new(f(&attachment.node.base)),
+                            bottom: attachment.node.bottom.as_deref().map(&mut f).map(Box::new),
+                            top: attachment.node.top.as_deref().map(&mut f).map(Box::new),
+                        },
+                    ))
+                }
+                PrimaryExpression::Matrix(matrix) => PrimaryExpression::Matrix(Node::new(
+                    matrix.span,
+                    Matrix {
+                        rows: matrix
+                            .node
+                            .rows
+                            .iter()
+                            .map(|row| {
+                                Node::new(
+                                    row.span,
+                                    Array {
+                                        elements: row.node.elements.iter().map(&mut f).collect(),
+                                    },
+                                )
+                            })
+                            .collect(),
+                    },
+                )),
+                PrimaryExpression::BigOperator(big_operator) => {
+                    PrimaryExpression::BigOperator(Node::new(
+                        big_operator.span,
+                        BigOperator {
+                            op: big_operator.node.op.clone(),
+                            index: big_operator.node.index.clone(),
+                            lower: big_operator.node.lower.as_deref().map(&mut f).map(Box::new),
+                            upper: big_operator.node.upper.as_deref().map(&mut f).map(Box::new),
+                            body: Box::new(f(&big_operator.node.body)),
+                        },
+                    ))
+                }
+                PrimaryExpression::Decoration(decoration) => {
+                    PrimaryExpression::Decoration(Node::new(
+                        decoration.span,
+                        Decoration {
+                            kind: decoration.node.kind.clone(),
+                            base: Box::new(f(&decoration.node.base)),
+                        },
+                    ))
+                }
+                PrimaryExpression::Cases(cases) => PrimaryExpression::Cases(Node::new(
+                    cases.span,
+                    Cases {
+                        cases: cases
+                            .node
+                            .cases
+                            .iter()
+                            .map(|case| {
+                                Node::new(
+                                    case.span,
+                                    Case {
+                                        value: f(&case.node.value),
+                                        text: case.node.text.clone(),
+                                        condition: case.node.condition.as_ref().map(&mut f),
+                                    },
+                                )
+                            })
+                            .collect(),
+                    },
+                )),
+                PrimaryExpression::Array(array) => PrimaryExpression::Array(Node::new(
+                    array.span,
+                    Array {
+                        elements: array.node.elements.iter().map(&mut f).collect(),
+                    },
+                )),
+                PrimaryExpression::Number(_)
+                | PrimaryExpression::Identifier(_)
+                | PrimaryExpression::Text(_)
+                | PrimaryExpression::Error(_) => return expression.clone(),
+            };
+            Expression::PrimaryExpression(Node::new(primary.span, mapped))
+        }
+    };
+    Node::new(span, node)
+}
+
+#[test]
+fn test_simplify() {
+    let parser = crate::parser::Parser::new("((x)) * 1 + 0 + 2*x + 3 - x^1 + 2^3");
+    let expression = parser.expression().unwrap();
+    assert_eq!(expression_to_typst(&simplify(&expression)), "2 dot x + 11");
+
+    let parser = crate::parser::Parser::new("1 + x*3 + (x^2)");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        expression_to_typst(&simplify(&expression)),
+        "x^(2) + 3 dot x + 1"
+    );
+}
+
+#[test]
+fn test_simplify_keeps_source_spans() {
+    let parser = crate::parser::Parser::new("y + x * 1");
+    let expression = parser.expression().unwrap();
+    let simplified = simplify(&expression);
+    let Expression::BinaryExpression(binary) = &simplified.node else {
+        panic!("expected a sum but found {:?}", simplified);
+    };
+    assert_eq!(expression_to_typst(&simplified), "x + y");
+    assert_eq!(
+        (binary.node.lhs.span.start, binary.node.lhs.span.end),
+        (4, 5)
+    );
+    assert_eq!(
+        (binary.node.rhs.span.start, binary.node.rhs.span.end),
+        (0, 1)
+    );
+}
+
+#[test]
+fn test_simplify_with_custom_rule() {
+    struct LnOfExp;
+
+    impl Rule for LnOfExp {
+        fn name(&self) -> &str {
+            "ln-of-exp"
+        }
+
+        fn apply(&self, expression: &Node<Expression>) -> Option<Node<Expression>> {
+            let Expression::PrimaryExpression(primary) = &expression.node else {
+                return None;
+            };
+            let PrimaryExpression::FunctionCall(ln) = &primary.node else {
+                return None;
+            };
+            let [argument] = ln.node.arguments.as_slice() else {
+                return None;
+            };
+            let Expression::PrimaryExpression(primary) = &argument.node else {
+                return None;
+            };
+            let PrimaryExpression::FunctionCall(exp) = &primary.node else {
+                return None;
+            };
+            match (ln.node.name.node.as_str(), exp.node.name.node.as_str()) {
+                ("ln", "exp") => exp.node.arguments.first().cloned(),
+                _ => None,
+            }
+        }
+    }
+
+    let mut simplifier = Simplifier::default();
+    simplifier.add_rule(Box::new(LnOfExp));
+    let parser = crate::parser::Parser::new("ln(exp(x + 0)) - x");
+    let expression = parser.expression().unwrap();
+    assert_eq!(expression_to_typst(&simplifier.simplify(&expression)), "0");
+}
+
+#[test]
+fn test_simplify_juxtaposed_products() {
+    let parser = crate::parser::Parser::new("2x + 1 + 3 a b + x*2");
+    let expression = parser.expression().unwrap();
+    assert_eq!(
+        expression_to_typst(&simplify(&expression)),
+        "3 a b + 4 x + 1"
+    );
+}
+
+#[test]
+fn test_simplify_folds_exactly() {
+    let cases = [
+        ("3^40", "12157665459056928801"),
+        ("2^127 - 1", "170141183460469231731687303715884105727"),
+        ("0.1 + 0.2", "0.3"),
+        ("1.5 * 0.2 - 2.5e-1", "0.05"),
+        ("0.6 / 0.2 + 2^(-1)", "2^(-1) + 3"),
+        ("1e400 + 1", "1e400 + 1"),
+        ("0.1 x + 0.2 x", "0.3 x"),
+    ];
+    for (input, expected) in cases {
+        let parser = crate::parser::Parser::new(input);
+        let expression = parser.expression().unwrap();
+        assert_eq!(
+            expression_to_typst(&simplify(&expression)),
+            expected,
+            "{}",
+            input
+        );
+    }
+}
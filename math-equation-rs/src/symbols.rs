@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+// Named symbols such as `alpha` or `infinity`. The renderers look identifiers
+// up here and emit the symbol instead of the spelled-out name.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub unicode: String, // Used as the MathML element text
+    pub latex: String,
+    pub typst: String,
+    pub operator: bool, // Rendered as <mo> rather than <mi> in MathML
+}
+
+impl Symbol {
+    pub fn new(unicode: &str, latex: &str, typst: &str) -> Symbol {
+        Symbol {
+            unicode: unicode.to_string(),
+            latex: latex.to_string(),
+            typst: typst.to_string(),
+            operator: false,
+        }
+    }
+
+    pub fn operator(unicode: &str, latex: &str, typst: &str) -> Symbol {
+        Symbol {
+            operator: true,
+            ..Symbol::new(unicode, latex, typst)
+        }
+    }
+
+    pub fn mathml_tag(&self) -> &'static str {
+        if self.operator {
+            "mo"
+        } else {
+            "mi"
+        }
+    }
+}
+
+// (name, code point) of the lower-case Greek letters. The upper-case letter is
+// the capitalized name, 0x20 code points below.
+const GREEK_LETTERS: &[(&str, u32)] = &[
+    ("alpha", 0x3b1),
+    ("beta", 0x3b2),
+    ("gamma", 0x3b3),
+    ("delta", 0x3b4),
+    ("epsilon", 0x3b5),
+    ("zeta", 0x3b6),
+    ("eta", 0x3b7),
+    ("theta", 0x3b8),
+    ("iota", 0x3b9),
+    ("kappa", 0x3ba),
+    ("lambda", 0x3bb),
+    ("mu", 0x3bc),
+    ("nu", 0x3bd),
+    ("xi", 0x3be),
+    ("omicron", 0x3bf),
+    ("pi", 0x3c0),
+    ("rho", 0x3c1),
+    ("sigma", 0x3c3),
+    ("tau", 0x3c4),
+    ("upsilon", 0x3c5),
+    ("phi", 0x3c6),
+    ("chi", 0x3c7),
+    ("psi", 0x3c8),
+    ("omega", 0x3c9),
+];
+
+// Upper-case Greek letters that LaTeX has a command for; the others look like
+// Latin capitals and are written as such.
+const LATEX_UPPER_CASE: &[&str] = &[
+    "Gamma", "Delta", "Theta", "Lambda", "Xi", "Pi", "Sigma", "Upsilon", "Phi", "Psi", "Omega",
+];
+
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    symbols: HashMap<String, Symbol>,
+}
+
+impl SymbolTable {
+    // A table without any symbols, so every identifier is rendered by name.
+    pub fn new() -> SymbolTable {
+        SymbolTable {
+            symbols: HashMap::new(),
+        }
+    }
+
+    // Adds a symbol, replacing any registered under the same name.
+    pub fn register(&mut self, name: &str, symbol: Symbol) {
+        self.symbols.insert(name.to_string(), symbol);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.get(name)
+    }
+
+    // The symbol written as `unicode`, so that input typed as `α` is rendered
+    // like input typed as `alpha`.
+    pub fn get_by_unicode(&self, unicode: &str) -> Option<&Symbol> {
+        self.symbols
+            .values()
+            .find(|symbol| symbol.unicode == unicode)
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> SymbolTable {
+        let mut table = SymbolTable::new();
+        for &(name, code_point) in GREEK_LETTERS {
+            let lower = char::from_u32(code_point).unwrap().to_string();
+            let latex = match name {
+                "omicron" => "o".to_string(),
+                _ => format!("\\{}", name),
+            };
+            table.register(name, Symbol::new(&lower, &latex, name));
+
+            let upper_name = name[..1].to_uppercase() + &name[1..];
+            let upper = char::from_u32(code_point - 0x20).unwrap().to_string();
+            let latex = if LATEX_UPPER_CASE.contains(&upper_name.as_str()) {
+                format!("\\{}", upper_name)
+            } else {
+                upper_name[..1].to_string()
+            };
+            table.register(&upper_name, Symbol::new(&upper, &latex, &upper_name));
+        }
+        table.register("infinity", Symbol::new("∞", "\\infty", "infinity"));
+        table.register("partial", Symbol::new("∂", "\\partial", "partial"));
+        table.register("nabla", Symbol::new("∇", "\\nabla", "nabla"));
+        table.register("emptyset", Symbol::new("∅", "\\emptyset", "emptyset"));
+        table.register("sum", Symbol::operator("∑", "\\sum", "sum"));
+        table.register("prod", Symbol::operator("∏", "\\prod", "product"));
+        table.register("integral", Symbol::operator("∫", "\\int", "integral"));
+        table
+    }
+}
+
+#[test]
+fn test_symbol_table() {
+    let mut symbols = SymbolTable::default();
+    assert_eq!(symbols.get("alpha").unwrap().unicode, "α");
+    assert_eq!(symbols.get("Gamma").unwrap().unicode, "Γ");
+    assert_eq!(symbols.get("Gamma").unwrap().latex, "\\Gamma");
+    assert_eq!(symbols.get("Alpha").unwrap().latex, "A");
+    assert_eq!(symbols.get("Omega").unwrap().unicode, "Ω");
+    assert!(symbols.get("sum").unwrap().operator);
+    assert_eq!(symbols.get("aleph"), None);
+    assert_eq!(symbols.get_by_unicode("Δ").unwrap().latex, "\\Delta");
+    assert_eq!(symbols.get_by_unicode("a"), None);
+
+    symbols.register("aleph", Symbol::new("ℵ", "\\aleph", "aleph"));
+    assert_eq!(symbols.get("aleph").unwrap().latex, "\\aleph");
+}
@@ -0,0 +1,392 @@
+use crate::ast::{
+    AlignedBlock, AlignedRow, Array, BigOperator, BigOperatorKind, BinaryExpression,
+    BinaryOperator, Cases, Decoration, DecorationKind, Expression, FunctionCall, MathEquation,
+    Matrix, Node, PrimaryExpression, UnaryExpression, UnaryOperator,
+};
+use crate::layout::LayoutFunction;
+use crate::symbols::SymbolTable;
+
+// Renders the AST into Typst math source (the contents of a `$ ... $` block).
+
+// Predefined text operators of Typst math, which can be called by name. The
+// layout functions share their names with Typst's own and are called by name
+// as well.
+const KNOWN_FUNCTIONS: &[&str] = &[
+    "arccos", "arcsin", "arctan", "cos", "cosh", "cot", "coth", "csc", "deg", "det", "exp", "gcd",
+    "lg", "ln", "log", "max", "min", "sec", "sin", "sinh", "tan", "tanh",
+];
+
+pub fn to_typst(math_equation: &Node<MathEquation>) -> String {
+    to_typst_with_symbols(math_equation, &SymbolTable::default())
+}
+
+pub fn to_typst_with_symbols(math_equation: &Node<MathEquation>, symbols: &SymbolTable) -> String {
+    let mut out = String::new();
+    for (i, expression) in math_equation.node.expressions.iter().enumerate() {
+        if i > 0 {
+            out.push_str(" = ");
+        }
+        write_expression(&mut out, symbols, expression);
+    }
+    out
+}
+
+// Typst math has the same `&` and `\` syntax for aligned lines.
+pub fn aligned_block_to_typst(aligned_block: &Node<AlignedBlock>) -> String {
+    aligned_block_to_typst_with_symbols(aligned_block, &SymbolTable::default())
+}
+
+pub fn aligned_block_to_typst_with_symbols(
+    aligned_block: &Node<AlignedBlock>,
+    symbols: &SymbolTable,
+) -> String {
+    let mut out = String::new();
+    for (i, row) in aligned_block.node.rows.iter().enumerate() {
+        if i > 0 {
+            out.push_str(" \\\n");
+        }
+        write_aligned_row(&mut out, symbols, &row.node);
+    }
+    out
+}
+
+pub fn expression_to_typst(expression: &Node<Expression>) -> String {
+    expression_to_typst_with_symbols(expression, &SymbolTable::default())
+}
+
+pub fn expression_to_typst_with_symbols(
+    expression: &Node<Expression>,
+    symbols: &SymbolTable,
+) -> String {
+    let mut out = String::new();
+    write_expression(&mut out, symbols, expression);
+    out
+}
+
+fn write_aligned_row(out: &mut String, symbols: &SymbolTable, row: &AlignedRow) {
+    if let Some(lhs) = &row.lhs {
+        write_expression(out, symbols, lhs);
+    }
+    for (i, rhs) in row.rhs.iter().enumerate() {
+        if i > 0 || row.lhs.is_some() {
+            out.push(' ');
+        }
+        let relation = match &row.relation {
+            Some(relation) if row.align == Some(i) => binary_operator(&relation.node),
+            _ => "=",
+        };
+        if row.align == Some(i) {
+            out.push('&');
+        }
+        out.push_str(relation);
+        out.push(' ');
+        write_expression(out, symbols, rhs);
+    }
+}
+
+fn write_expression(out: &mut String, symbols: &SymbolTable, expression: &Node<Expression>) {
+    match &expression.node {
+        Expression::UnaryExpression(unary) => write_unary(out, symbols, &unary.node),
+        Expression::BinaryExpression(binary) => write_binary(out, symbols, &binary.node),
+        Expression::PrimaryExpression(primary) => write_primary(out, symbols, &primary.node),
+    }
+}
+
+fn write_unary(out: &mut String, symbols: &SymbolTable, unary: &UnaryExpression) {
+    if unary.is_prefix {
+        if let Some(op) = &unary.op {
+            out.push_str(unary_operator(&op.node));
+        }
+        write_expression(out, symbols, &unary.expr);
+    } else {
+        write_expression(out, symbols, &unary.expr);
+        if let Some(op) = &unary.op {
+            out.push_str(unary_operator(&op.node));
+        }
+    }
+}
+
+fn write_binary(out: &mut String, symbols: &SymbolTable, binary: &BinaryExpression) {
+    match binary.op.node {
+        BinaryOperator::Div => {
+            out.push_str("frac(");
+            write_expression(out, symbols, ungrouped(&binary.lhs));
+            out.push_str(", ");
+            write_expression(out, symbols, ungrouped(&binary.rhs));
+            out.push(')');
+        }
+        BinaryOperator::Pow => {
+            write_expression(out, symbols, &binary.lhs);
+            out.push_str("^(");
+            write_expression(out, symbols, ungrouped(&binary.rhs));
+            out.push(')');
+        }
+        _ if binary.implicit => {
+            write_expression(out, symbols, &binary.lhs);
+            out.push(' ');
+            write_expression(out, symbols, &binary.rhs);
+        }
+        _ => {
+            write_expression(out, symbols, &binary.lhs);
+            out.push(' ');
+            out.push_str(binary_operator(&binary.op.node));
+            out.push(' ');
+            write_expression(out, symbols, &binary.rhs);
+        }
+    }
+}
+
+fn write_primary(out: &mut String, symbols: &SymbolTable, primary: &PrimaryExpression) {
+    match primary {
+        PrimaryExpression::Number(number) => out.push_str(&number.node.to_string()),
+        PrimaryExpression::Identifier(identifier) => {
+            write_identifier(out, symbols, &identifier.node)
+        }
+        PrimaryExpression::Array(array) => write_array(out, symbols, &array.node),
+        PrimaryExpression::Matrix(matrix) => write_matrix(out, symbols, &matrix.node),
+        PrimaryExpression::Cases(cases) => write_cases(out, symbols, &cases.node),
+        PrimaryExpression::BigOperator(big_operator) => {
+            write_big_operator(out, symbols, &big_operator.node)
+        }
+        PrimaryExpression::Decoration(decoration) => {
+            write_decoration(out, symbols, &decoration.node)
+        }
+        PrimaryExpression::FunctionCall(function_call) => {
+            write_function_call(out, symbols, &function_call.node)
+        }
+        PrimaryExpression::GroupedExpression(expr) => {
+            out.push('(');
+            write_expression(out, symbols, expr);
+            out.push(')');
+        }
+        PrimaryExpression::Attachment(attachment) => {
+            write_expression(out, symbols, &attachment.node.base);
+            if let Some(bottom) = &attachment.node.bottom {
+                out.push_str("_(");
+                write_expression(out, symbols, ungrouped(bottom));
+                out.push(')');
+            }
+            if let Some(top) = &attachment.node.top {
+                out.push_str("^(");
+                write_expression(out, symbols, ungrouped(top));
+                out.push(')');
+            }
+        }
+        PrimaryExpression::Text(text) => write_string(out, &text.node),
+        PrimaryExpression::Error(text) => {
+            out.push_str("#text(fill: red)[");
+            write_string(
+                out,
+                if text.node.is_empty() {
+                    "?"
+                } else {
+                    &text.node
+                },
+            );
+            out.push(']');
+        }
+    }
+}
+
+fn write_identifier(out: &mut String, symbols: &SymbolTable, identifier: &str) {
+    // Typst reads a run of several letters as a variable name, so anything
+    // longer than a single letter is set as text to keep it from being resolved,
+    // unless it names a known symbol.
+    if let Some(symbol) = symbols.get(identifier) {
+        out.push_str(&symbol.typst);
+    } else if identifier.chars().count() == 1 {
+        out.push_str(identifier);
+    } else {
+        write_string(out, identifier);
+    }
+}
+
+fn write_array(out: &mut String, symbols: &SymbolTable, array: &Array) {
+    out.push_str("vec(");
+    write_separated(out, symbols, &array.elements);
+    out.push(')');
+}
+
+fn write_matrix(out: &mut String, symbols: &SymbolTable, matrix: &Matrix) {
+    out.push_str("mat(");
+    for (i, row) in matrix.rows.iter().enumerate() {
+        if i > 0 {
+            out.push_str("; ");
+        }
+        write_separated(out, symbols, &row.node.elements);
+    }
+    out.push(')');
+}
+
+fn write_cases(out: &mut String, symbols: &SymbolTable, cases: &Cases) {
+    out.push_str("cases(");
+    for (i, case) in cases.cases.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_expression(out, symbols, &case.node.value);
+        if let Some(text) = &case.node.text {
+            out.push_str(" &");
+            write_string(out, &text.node);
+        }
+        if let Some(condition) = &case.node.condition {
+            out.push(' ');
+            write_expression(out, symbols, condition);
+        }
+    }
+    out.push(')');
+}
+
+fn write_big_operator(out: &mut String, symbols: &SymbolTable, big_operator: &BigOperator) {
+    out.push_str(big_operator_name(&big_operator.op.node));
+    if let Some(lower) = &big_operator.lower {
+        out.push_str("_(");
+        if let Some(index) = &big_operator.index {
+            write_identifier(out, symbols, &index.node);
+            out.push_str(" = ");
+        }
+        write_expression(out, symbols, ungrouped(lower));
+        out.push(')');
+    }
+    if let Some(upper) = &big_operator.upper {
+        out.push_str("^(");
+        write_expression(out, symbols, ungrouped(upper));
+        out.push(')');
+    }
+    out.push(' ');
+    write_expression(out, symbols, &big_operator.body);
+}
+
+fn write_decoration(out: &mut String, symbols: &SymbolTable, decoration: &Decoration) {
+    out.push_str(decoration_name(&decoration.kind.node));
+    out.push('(');
+    write_expression(out, symbols, ungrouped(&decoration.base));
+    out.push(')');
+}
+
+fn write_function_call(out: &mut String, symbols: &SymbolTable, function_call: &FunctionCall) {
+    let name = function_call.name.node.as_str();
+    if LayoutFunction::from_call(function_call).is_some()
+        || KNOWN_FUNCTIONS.contains(&name)
+        || name.chars().count() == 1
+    {
+        out.push_str(name);
+    } else {
+        out.push_str("op(");
+        write_string(out, name);
+        out.push(')');
+    }
+    out.push('(');
+    write_separated(out, symbols, &function_call.arguments);
+    out.push(')');
+}
+
+fn write_separated(out: &mut String, symbols: &SymbolTable, expressions: &[Node<Expression>]) {
+    for (i, expression) in expressions.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_expression(out, symbols, expression);
+    }
+}
+
+fn write_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+}
+
+fn ungrouped(expression: &Node<Expression>) -> &Node<Expression> {
+    if let Expression::PrimaryExpression(primary) = &expression.node {
+        if let PrimaryExpression::GroupedExpression(inner) = &primary.node {
+            return inner;
+        }
+    }
+    expression
+}
+
+fn binary_operator(op: &BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Sub => "-",
+        BinaryOperator::Mul => "dot",
+        BinaryOperator::Div => "/",
+        BinaryOperator::Pow => "^",
+        BinaryOperator::Eq => "equiv",
+        BinaryOperator::Ne => "!=",
+        BinaryOperator::Lt => "<",
+        BinaryOperator::Gt => ">",
+        BinaryOperator::Le => "<=",
+        BinaryOperator::Ge => ">=",
+        BinaryOperator::And => "and",
+        BinaryOperator::Or => "or",
+        BinaryOperator::Arrow => "->",
+        BinaryOperator::Implies => "=>",
+        BinaryOperator::Iff => "<=>",
+        BinaryOperator::Sim => "tilde.op",
+        BinaryOperator::Cong => "tilde.equiv",
+        BinaryOperator::Define => ":=",
+        BinaryOperator::MuchLess => "<<",
+        BinaryOperator::MuchGreater => ">>",
+        BinaryOperator::In => "in",
+        BinaryOperator::Subset => "subset",
+    }
+}
+
+fn big_operator_name(op: &BigOperatorKind) -> &'static str {
+    match op {
+        BigOperatorKind::Sum => "sum",
+        BigOperatorKind::Prod => "product",
+        BigOperatorKind::Integral => "integral",
+        BigOperatorKind::Lim => "lim",
+    }
+}
+
+// Typst's `vec` is a column vector, so the arrow accent is spelled out.
+fn decoration_name(kind: &DecorationKind) -> &'static str {
+    match kind {
+        DecorationKind::Hat => "hat",
+        DecorationKind::Bar => "macron",
+        DecorationKind::Tilde => "tilde",
+        DecorationKind::Vec => "arrow",
+        DecorationKind::Dot => "dot",
+        DecorationKind::Ddot => "dot.double",
+        DecorationKind::Overline => "overline",
+        DecorationKind::Underbrace => "underbrace",
+    }
+}
+
+fn unary_operator(op: &UnaryOperator) -> &'static str {
+    match op {
+        UnaryOperator::Neg => "-",
+        UnaryOperator::Not => "not ",
+        UnaryOperator::Inc => "++",
+        UnaryOperator::Dec => "--",
+    }
+}
+
+#[test]
+fn test_to_typst() {
+    let parser = crate::parser::Parser::new("sqrt(x^2 + 1) / (2 * foo(y)) = [a, b]");
+    let math_equation = parser.math_equation().unwrap();
+    assert_eq!(
+        to_typst(&math_equation),
+        "frac(sqrt(x^(2) + 1), 2 dot op(\"foo\")(y)) = vec(a, b)"
+    );
+}
+
+#[test]
+fn test_to_typst_matrix() {
+    let parser = crate::parser::Parser::new("[[1, 0], [0, 1]]");
+    let expression = parser.expression().unwrap();
+    assert_eq!(expression_to_typst(&expression), "mat(1, 0; 0, 1)");
+
+    let parser = crate::parser::Parser::new("[[1, 0], x]");
+    let expression = parser.expression().unwrap();
+    assert_eq!(expression_to_typst(&expression), "vec(vec(1, 0), x)");
+}